@@ -0,0 +1,145 @@
+
+use {
+    breakout::game::{self, Controller, Progress, DT, FRAMERATE},
+    std::fmt::Write,
+};
+
+const DEFAULT_MAX_SECONDS: u64 = 30 * 60;
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+fn usage() -> ! {
+    fail("usage: headless [--seed N] [--max-ticks N] \
+          (--replay FILE | --script FILE | --autopilot [--aim]) [LEVEL...]")
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"'  => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => { write!(out, "\\u{:04x}", c as u32).unwrap(); }
+            c    => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_scoring(out: &mut String, scoring: &game::Scoring) {
+    write!(out,
+        "\"score\": {}, \"bonus\": {}, \"penalties\": {}, \"combo_max\": {}, \"rank\": \"{}\"",
+        scoring.score,
+        scoring.bonus,
+        scoring.penalties,
+        scoring.combo_max,
+        scoring.rank()
+    ).unwrap();
+}
+
+fn report(campaign: &game::Campaign, progress: Progress, ticks: u64) -> String {
+    let status = match progress {
+        Progress::Finished => "finished",
+        Progress::GameOver => "game_over",
+        _                  => "incomplete",
+    };
+
+    let mut out = String::new();
+    writeln!(out, "{{").unwrap();
+    writeln!(out, "  \"status\": \"{}\",", status).unwrap();
+    writeln!(out, "  \"ticks\": {},", ticks).unwrap();
+    writeln!(out, "  \"lives\": {},", campaign.lives()).unwrap();
+
+    let levels: Vec<String> = campaign.summaries().iter()
+        .map(|summary| {
+            let mut entry = format!("    {{\"name\": {}, ", json_string(&summary.name));
+            json_scoring(&mut entry, &summary.scoring);
+            entry.push('}');
+            entry
+        })
+        .collect();
+
+    if levels.is_empty() {
+        writeln!(out, "  \"levels\": [],").unwrap();
+    }
+    else {
+        writeln!(out, "  \"levels\": [\n{}\n  ],", levels.join(",\n")).unwrap();
+    }
+
+    write!(out, "  ").unwrap();
+    json_scoring(&mut out, &campaign.totals());
+    writeln!(out).unwrap();
+    write!(out, "}}").unwrap();
+    out
+}
+
+fn main() {
+    let mut seed = 0;
+    let mut max_ticks = DEFAULT_MAX_SECONDS * FRAMERATE as u64;
+    let mut levels = Vec::new();
+    let mut replay = None;
+    let mut script = None;
+    let mut autopilot = false;
+    let mut aim = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--seed"      => seed      = value().parse().unwrap_or_else(|_| usage()),
+            "--max-ticks" => max_ticks = value().parse().unwrap_or_else(|_| usage()),
+            "--replay"    => replay    = Some(value()),
+            "--script"    => script    = Some(value()),
+            "--autopilot" => autopilot = true,
+            "--aim"       => aim       = true,
+            _ if arg.starts_with("--") => usage(),
+            _ => levels.push(game::LevelSource::parse(&arg)),
+        }
+    }
+
+    let replay = replay.map(|path| game::Replay::load(&path)
+        .unwrap_or_else(|err| fail(format!("{}: {}", path, err))));
+    let script = script.map(|path| game::Script::load(&path)
+        .unwrap_or_else(|err| fail(format!("{}: {}", path, err))));
+
+    let mut controller: Box<dyn Controller> = match (&replay, &script, autopilot) {
+        (Some(replay), None, false) => {
+            seed = replay.seed;
+            levels = replay.levels.clone();
+            Box::new(replay.playback())
+        }
+
+        (None, Some(script), false) => Box::new(script.inputs()),
+        (None, None, true)          => Box::new(game::Autopilot::new(aim)),
+
+        _ => usage(),
+    };
+
+    if levels.is_empty() {
+        levels.push(game::LevelSource::Procedural(seed));
+    }
+
+    let mut campaign = game::Campaign::new(seed, &levels)
+        .unwrap_or_else(|err| fail(err));
+
+    let mut progress = Progress::Playing;
+    let mut ticks = 0;
+    while ticks < max_ticks {
+        let input = match controller.input(campaign.state()) {
+            Some(input) => input,
+            None        => { break; }
+        };
+
+        progress = campaign.update(DT, input);
+        ticks += 1;
+        if progress.is_over() {
+            break;
+        }
+    }
+
+    println!("{}", report(&campaign, progress, ticks));
+}
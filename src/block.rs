@@ -3,38 +3,177 @@ use {
     crate::math::*,
 };
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Kind {
     Invlunerable,
-    Scoring { score: i32, hp: i32 }
+    Scoring      { score: i32, hp: i32 },
+    // takes its neighbours with it when broken
+    Explosive    { score: i32, hp: i32 },
+    // shrugs off a ball slower than `min_speed`
+    Armored      { score: i32, hp: i32, min_speed: f32 },
+    // heals a point `delay` seconds after it was last hit, and every `delay` after
+    // that, back up to `max_hp`
+    Regenerating { score: i32, hp: i32, max_hp: i32, delay: f32, timer: f32 },
+    // opens or closes every gate sharing its `link` when hit
+    Switch       { link: u32 },
+    // solid while closed, and no obstacle at all while open
+    Gate         { link: u32, open: bool },
 }
 
+impl Kind {
+    pub fn regenerating(score: i32, hp: i32, delay: f32) -> Kind {
+        Kind::Regenerating { score, hp, max_hp: hp, delay, timer: delay }
+    }
+}
+
+// Carries a block around a loop of waypoints at a steady pace. The waypoints are
+// offsets from where the block starts, and the loop closes back at the start.
+#[derive(Clone, Debug)]
+pub struct Patrol {
+    home:      Rect,
+    waypoints: Vec<V2>,
+    pace:      f32,
+    length:    f32,
+    travelled: f32,
+    prev_rect: Rect,
+}
+
+impl Patrol {
+    // Visits each of `offsets` in turn, then comes back the way it went, or straight
+    // home from the last if `looped`.
+    pub fn new(home: Rect, offsets: &[V2], pace: f32, looped: bool) -> Patrol {
+        let mut waypoints = vec![V2::zeros()];
+        waypoints.extend_from_slice(offsets);
+        if !looped && offsets.len() > 1 {
+            waypoints.extend(offsets[.. offsets.len() - 1].iter().rev());
+        }
+
+        let mut patrol = Patrol { home, waypoints, pace, length: 0., travelled: 0., prev_rect: home };
+        patrol.length = patrol.legs().map(|(from, to)| (to - from).norm()).sum();
+        patrol
+    }
+
+    fn legs(&self) -> impl Iterator<Item = (V2, V2)> + '_ {
+        let next = self.waypoints.iter().cycle().skip(1);
+        self.waypoints.iter().copied().zip(next.copied())
+    }
+
+    fn offset(&self) -> V2 {
+        let mut left = self.travelled;
+        for (from, to) in self.legs() {
+            let leg = (to - from).norm();
+            if left < leg {
+                return from + (to - from) * (left / leg);
+            }
+            left -= leg;
+        }
+
+        V2::zeros()
+    }
+
+    // Everywhere the block goes.
+    pub fn bounds(&self) -> Rect {
+        self.waypoints.iter()
+            .fold(self.home, |bounds, offset| bounds.union(&self.home.at(P2::from(*offset))))
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Block {
-    pub rect: Rect,
-    pub kind: Kind,
+    pub rect:   Rect,
+    pub kind:   Kind,
+    pub patrol: Option<Patrol>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Hit {
     Broken(i32),
+    // broken, and its neighbours should be hit in turn
+    Exploded(i32),
     Damaged,
+    // an armored block hit too slowly
+    Deflected,
+    // a switch, for the gates with this link
+    Switched(u32),
     Invlunerable
 }
 
 impl Block {
-    pub fn hit(&mut self) -> Hit {
-        if let Kind::Scoring { score, hp } = &mut self.kind {
-            *hp -= 1;
-            if *hp == 0 { Hit::Broken(*score) }
-            else        { Hit::Damaged }
+    // `speed` is how hard the block was hit; armour holds against anything slower
+    // than its `min_speed`.
+    pub fn hit(&mut self, speed: f32) -> Hit {
+        use Kind::*;
+        let (score, hp) = match &mut self.kind {
+            Invlunerable | Gate { .. } => { return Hit::Invlunerable; }
+
+            Switch { link } => { return Hit::Switched(*link); }
+
+            Armored { min_speed, .. } if speed < *min_speed => { return Hit::Deflected; }
+
+            Scoring   { score, hp }
+            | Explosive { score, hp }
+            | Armored   { score, hp, .. } => (*score, hp),
+
+            Regenerating { score, hp, delay, timer, .. } => {
+                *timer = *delay;
+                (*score, hp)
+            }
+        };
+
+        *hp -= 1;
+        if *hp > 0 {
+            return Hit::Damaged;
         }
-        else {
-            Hit::Invlunerable
+
+        match self.kind {
+            Explosive { .. } => Hit::Exploded(score),
+            _                => Hit::Broken(score),
+        }
+    }
+
+    // Moves the block along its patrol, and returns whether it healed.
+    pub fn update(&mut self, dt: f32) -> bool {
+        if let Some(patrol) = &mut self.patrol {
+            patrol.prev_rect = self.rect;
+            if patrol.length > 0. {
+                patrol.travelled = (patrol.travelled + patrol.pace * dt).rem_euclid(patrol.length);
+            }
+            self.rect = patrol.home.at(P2::from(patrol.offset()));
         }
+
+        if let Kind::Regenerating { hp, max_hp, delay, timer, .. } = &mut self.kind {
+            if *hp < *max_hp {
+                *timer -= dt;
+                if *timer <= 0. {
+                    *hp += 1;
+                    *timer = *delay;
+                    return true;
+                }
+            }
+        }
+
+        false
     }
 
     pub fn hp(&self) -> Option<i32> {
+        use Kind::*;
         match self.kind {
-            Kind::Scoring { hp, .. } => Some(hp),
-            _                        => None
+            Scoring { hp, .. }
+            | Explosive { hp, .. }
+            | Armored { hp, .. }
+            | Regenerating { hp, .. } => Some(hp),
+
+            Invlunerable
+            | Switch { .. }
+            | Gate { .. }             => None
+        }
+    }
+
+    // Whether the ball bounces off it; open gates let it through.
+    pub fn is_solid(&self) -> bool {
+        match self.kind {
+            Kind::Gate { open, .. } => !open,
+            _                       => true,
         }
     }
 
@@ -42,4 +181,86 @@ impl Block {
         self.hp().is_some()
     }
 
+    pub fn is_moving(&self) -> bool {
+        self.patrol.is_some()
+    }
+
+    // Where the block was before the latest update.
+    pub fn prev_rect(&self) -> Rect {
+        self.patrol.as_ref().map_or(self.rect, |patrol| patrol.prev_rect)
+    }
+
+    // How fast it moved over the latest update, taking `dt`.
+    pub fn velocity(&self, dt: f32) -> V2 {
+        (self.rect.mins - self.prev_rect().mins) / dt
+    }
+
+    pub fn rect_at(&self, alpha: f32) -> Rect {
+        let prev = self.prev_rect();
+        let offset = (self.rect.mins - prev.mins) * alpha;
+        prev.at(P2::from(offset))
+    }
+
+    // The area the block covers, over the whole of its patrol if it has one.
+    pub fn bounds(&self) -> Rect {
+        self.patrol.as_ref().map_or(self.rect, Patrol::bounds)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(kind: Kind) -> Block {
+        Block { rect: Rect::new_square_centered(10.), kind, patrol: None }
+    }
+
+    #[test]
+    fn test_armor_holds_against_slow_hits() {
+        let mut armored = block(Kind::Armored { score: 10, hp: 2, min_speed: 500. });
+        assert_eq!(armored.hit(499.), Hit::Deflected);
+        assert_eq!(armored.hp(), Some(2));
+        assert_eq!(armored.hit(500.), Hit::Damaged);
+        assert_eq!(armored.hit(600.), Hit::Broken(10));
+    }
+
+    #[test]
+    fn test_regeneration_waits_for_the_last_hit() {
+        let mut regen = block(Kind::regenerating(10, 3, 1.));
+        regen.hit(0.);
+        regen.update(0.75);
+        regen.hit(0.);
+        assert_eq!(regen.hp(), Some(1));
+
+        // a second after the latest hit, and again a second after that
+        assert!(!regen.update(0.75));
+        assert!(regen.update(0.25));
+        assert_eq!(regen.hp(), Some(2));
+        assert!(regen.update(1.));
+        assert!(!regen.update(10.));
+        assert_eq!(regen.hp(), Some(3));
+    }
+
+    #[test]
+    fn test_patrol_goes_out_and_back() {
+        let mut moving = block(Kind::Invlunerable);
+        let offsets = [V2::new(10., 0.), V2::new(10., 10.)];
+        moving.patrol = Some(Patrol::new(moving.rect, &offsets, 10., false));
+        assert_eq!(moving.bounds(), Rect::new(P2::new(-5., -5.), P2::new(15., 15.)));
+
+        let mut positions = Vec::new();
+        for _ in 0 .. 8 {
+            moving.update(0.5);
+            positions.push(moving.rect.mins + V2::new(5., 5.));
+        }
+
+        let expected = [(5., 0.), (10., 0.), (10., 5.), (10., 10.), (10., 5.), (10., 0.), (5., 0.), (0., 0.)];
+        for (position, &(x, y)) in positions.iter().zip(expected.iter()) {
+            assert!((position - P2::new(x, y)).norm() < 0.001, "{} is not ({}, {})", position, x, y);
+        }
+
+        assert_eq!(moving.prev_rect().mins, P2::new(0., -5.));
+        assert_eq!(moving.rect_at(0.5).mins, P2::new(-2.5, -5.));
+    }
 }
@@ -5,9 +5,52 @@ use {
     },
 };
 
+// A circular arc, solid on its convex side, bounded by the outward normals at each
+// end, counter-clockwise from `from` to `to`.
+#[derive(Clone, Copy, Debug)]
+pub struct Arc {
+    pub center: P2,
+    pub radius: f32,
+    pub from:   V2,
+    pub to:     V2,
+}
+
+impl Arc {
+    fn covers(&self, normal: V2) -> bool {
+        let cross = |u: V2, w: V2| u.x * w.y - u.y * w.x;
+        cross(self.from, normal) >= 0. && cross(normal, self.to) >= 0.
+    }
+
+    pub fn intersect_with(&self, line: &impl Linear) -> Option<Collision> {
+        let whole = line.whole_line();
+        let stride = line.stride();
+        let offset = whole.at(0.) - self.center;
+
+        // only the entering root of |offset + t stride| = radius
+        let a = stride.norm_squared();
+        let b = offset.dot(&stride);
+        let c = offset.norm_squared() - self.radius * self.radius;
+        let discriminant = b * b - a * c;
+        if a < 1e-12 || discriminant < 0. {
+            return None;
+        }
+
+        let param = (-b - discriminant.sqrt()) / a;
+        if !line.parameter_on(param) {
+            return None;
+        }
+
+        let point = whole.at(param);
+        let normal = (point - self.center) / self.radius;
+        if self.covers(normal) { Some(Collision { param, point, normal }) }
+        else                   { None }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Collider {
     edges: Vec<Segment>, // TODO use smallvec
+    arcs:  Vec<Arc>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -19,17 +62,26 @@ pub struct Collision {
 
 impl Collider {
     pub fn new(edges: Vec<Segment>) -> Collider {
-        Collider { edges }
+        Collider { edges, arcs: Vec::new() }
+    }
+
+    pub fn with_arcs(edges: Vec<Segment>, arcs: Vec<Arc>) -> Collider {
+        Collider { edges, arcs }
     }
 
     pub fn intersect_with(&self, line: impl Linear) -> Option<Collision> {
-        self.edges.iter().copied()
+        let edge_hits = self.edges.iter().copied()
             .map(|edge| (edge, right(edge.direction())))
             .filter(|(_, normal)| normal.dot(&line.stride()) < 0.)
             .filter_map(|(side, normal)| {
                 line.intersect(&side)
                     .map(|ixn| Collision { param: ixn.lambda, point: ixn.point, normal })
-            })
+            });
+
+        let arc_hits = self.arcs.iter()
+            .filter_map(|arc| arc.intersect_with(&line));
+
+        edge_hits.chain(arc_hits)
             .filter(|collision| collision.param > 0.00001)
             .min_by_key(|collision| OrdF32(collision.param))
     }
@@ -40,7 +92,54 @@ impl std::iter::FromIterator<Segment> for Collider {
         where T: IntoIterator<Item = Segment>
     {
         let edges = iter.into_iter().collect();
-        Collider { edges }
+        Collider::new(edges)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{dilate::Dilate, math::rect::CollideFrom},
+    };
+
+    #[test]
+    fn test_rounded_corner_normal() {
+        let collider = Rect::new_square_centered(2.)
+            .expand(Circle::new_centered(1.))
+            .to_collider(CollideFrom::Outside);
+
+        // aimed straight at the top right corner
+        let motion = Segment::new(P2::new(4., 4.), V2::new(-4., -4.));
+        let hit = collider.intersect_with(motion).unwrap();
+        let diagonal = V2::new(1., 1.).normalize();
+        assert!((hit.normal - diagonal).norm() < 0.0001);
+        assert!((hit.point - (P2::new(1., 1.) + diagonal)).norm() < 0.0001);
+
+        // past the side, the flat edge is hit as before
+        let motion = Segment::new(P2::new(0.5, 4.), V2::new(0., -4.));
+        let hit = collider.intersect_with(motion).unwrap();
+        assert_eq!(hit.normal, V2::new(0., 1.));
+        assert!((hit.point.y - 2.).abs() < 0.0001);
+
+        // skimming the corner glances off at an angle, and just above it, misses
+        let motion = Segment::new(P2::new(4., 1.8), V2::new(-8., 0.));
+        let hit = collider.intersect_with(motion).unwrap();
+        assert!(hit.normal.x > 0. && hit.normal.y > 0.);
+
+        let motion = Segment::new(P2::new(4., 2.01), V2::new(-8., 0.));
+        assert!(collider.intersect_with(motion).is_none());
+    }
+
+    #[test]
+    fn test_inside_corner_is_closed() {
+        let collider = Rect::new_square_centered(4.)
+            .expand(Circle::new_centered(1.))
+            .to_collider(CollideFrom::Inside);
+
+        // heading out through the corner, where the sides meet
+        let motion = Segment::new(P2::new(1.5, 1.5), V2::new(2., 2.));
+        let hit = collider.intersect_with(motion).unwrap();
+        assert!((hit.point - P2::new(3., 3.)).norm() < 0.0001);
+    }
+}
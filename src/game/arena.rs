@@ -0,0 +1,156 @@
+
+// A generational arena: values are reached through `Id`s which stay valid until that
+// value is removed, and never come to refer to anything else afterwards, even when
+// its slot is reused.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Id {
+    index:      u32,
+    generation: u32,
+}
+
+#[derive(Clone, Debug)]
+struct Slot<T> {
+    generation: u32,
+    value:      Option<T>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    free:  Vec<u32>,
+    len:   usize,
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Arena<T> {
+        Arena { slots: Vec::new(), free: Vec::new(), len: 0 }
+    }
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Arena<T> {
+        Arena::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, value: T) -> Id {
+        self.len += 1;
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.generation += 1;
+                slot.value = Some(value);
+                Id { index, generation: slot.generation }
+            }
+
+            None => {
+                let index = self.slots.len() as u32;
+                self.slots.push(Slot { generation: 0, value: Some(value) });
+                Id { index, generation: 0 }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: Id) -> Option<T> {
+        let slot = self.slots.get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?;
+        let value = slot.value.take()?;
+        self.free.push(id.index);
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn contains(&self, id: Id) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn get(&self, id: Id) -> Option<&T> {
+        self.slots.get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: Id) -> Option<&mut T> {
+        self.slots.get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.value.as_mut())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Id, &T)> {
+        self.slots.iter()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let id = Id { index: index as u32, generation: slot.generation };
+                slot.value.as_ref().map(|value| (id, value))
+            })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Id, &mut T)> {
+        self.slots.iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let id = Id { index: index as u32, generation: slot.generation };
+                slot.value.as_mut().map(|value| (id, value))
+            })
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(|slot| slot.value.as_ref())
+    }
+}
+
+impl<T> std::ops::Index<Id> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, id: Id) -> &T {
+        self.get(id).expect("stale arena id")
+    }
+}
+
+impl<T> std::ops::IndexMut<Id> for Arena<T> {
+    fn index_mut(&mut self, id: Id) -> &mut T {
+        self.get_mut(id).expect("stale arena id")
+    }
+}
+
+impl<T> std::iter::FromIterator<T> for Arena<T> {
+    fn from_iter<I> (iter: I) -> Arena<T>
+        where I: IntoIterator<Item = T>
+    {
+        let mut arena = Arena::new();
+        for value in iter {
+            arena.insert(value);
+        }
+        arena
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_survive_removal() {
+        let mut arena: Arena<&str> = vec!["a", "b", "c"].into_iter().collect();
+        let ids: Vec<Id> = arena.iter().map(|(id, _)| id).collect();
+
+        assert_eq!(arena.remove(ids[0]), Some("a"));
+        assert_eq!(arena.remove(ids[0]), None);
+        assert_eq!(arena.get(ids[2]), Some(&"c"));
+        assert_eq!(arena.len(), 2);
+
+        // the freed slot is reused, but the old id doesn't see the new value
+        let d = arena.insert("d");
+        assert!(!arena.contains(ids[0]));
+        assert_eq!(arena.get(d), Some(&"d"));
+        assert_eq!(arena.values().copied().collect::<Vec<_>>(), vec!["d", "b", "c"]);
+    }
+}
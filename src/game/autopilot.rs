@@ -0,0 +1,141 @@
+
+use {
+    super::*,
+};
+
+// How far ahead of the paddle's coasting position the autopilot steers, and how
+// close it has to get before it lets go of the controls.
+const AIM_OFFSET:  f32 = 0.3;
+const DEADBAND:    f32 = 2.;
+const SERVE_SPEED: f32 = 100.;
+
+pub trait Controller {
+    // Returns `None` once the controller has nothing more to say, e.g. at the end of a replay.
+    fn input(&mut self, state: &State) -> Option<Input>;
+}
+
+impl<I> Controller for I
+    where I: Iterator<Item = Input>
+{
+    fn input(&mut self, _: &State) -> Option<Input> {
+        self.next()
+    }
+}
+
+// Tracks the ball, predicting where it will cross the paddle by folding its path
+// off the side walls. Blocks are ignored; the paddle simply re-plans each tick.
+pub struct Autopilot {
+    aim: bool,
+}
+
+impl Autopilot {
+    pub fn new(aim: bool) -> Autopilot {
+        Autopilot { aim }
+    }
+
+    // Where and when `ball` will next come down to paddle height, if it's descending.
+    fn intercept(state: &State, ball: &FlyingBall) -> Option<(f32, f32)> {
+        if ball.vel.y >= 0. {
+            return None;
+        }
+
+        let contact_y = PADDLE_Y + state.paddle_rect.maxs.y + state.ball.radius;
+        let time = (ball.pos.y - contact_y) / -ball.vel.y;
+        if time < 0. {
+            return None;
+        }
+
+        let bounds = walls_rect().expand(state.ball.radius);
+        let width = bounds.width();
+        let x = (ball.pos.x + ball.vel.x * time - bounds.mins.x).rem_euclid(2. * width);
+        let x = if x > width { 2. * width - x } else { x };
+
+        Some((bounds.mins.x + x, time))
+    }
+
+    // The horizontal direction we'd like the ball to leave the paddle in.
+    fn aim_direction(state: &State, from_x: f32) -> f32 {
+        let target = state.blocks.values()
+            .filter(|block| block.is_scoring())
+            .min_by_key(|block| OrdF32(block.rect.mins.y));
+
+        match target {
+            Some(block) => {
+                let target_x = block.rect.mins.x + block.rect.width() * 0.5;
+                (target_x - from_x).signum()
+            }
+            None => 0.
+        }
+    }
+
+    fn target_x(&self, state: &State) -> f32 {
+        let next = state.balls.iter()
+            .filter_map(|ball| Autopilot::intercept(state, ball))
+            .min_by_key(|(_, time)| OrdF32(*time));
+
+        let x = match next {
+            Some((x, _)) => x,
+            None => match state.balls.iter().min_by_key(|ball| OrdF32(ball.pos.y)) {
+                Some(ball) => ball.pos.x,
+                None       => state.paddle_x,
+            }
+        };
+
+        if self.aim {
+            // strike the ball off-centre on the side away from the target
+            let direction = Autopilot::aim_direction(state, x);
+            x - direction * AIM_OFFSET * state.paddle_rect.width() * 0.5
+        }
+        else {
+            x
+        }
+    }
+}
+
+impl Controller for Autopilot {
+    fn input(&mut self, state: &State) -> Option<Input> {
+        if state.balls.is_empty() && state.held.is_empty() {
+            // serve on the move so the ball goes off at an angle, turning back at the walls
+            let paddle_dir =
+                if      state.paddle_vel != 0. { state.paddle_vel.signum() as i32 }
+                else if state.paddle_x > 0.    { -1 }
+                else                           {  1 };
+            let serve = state.paddle_vel.abs() > SERVE_SPEED;
+            return Some(Input { paddle_dir, serve });
+        }
+
+        let target = self.target_x(state);
+
+        // with the stick released, friction brings the paddle to rest after vel / friction
+        let coast_x = state.paddle_x + state.paddle_vel / PADDLE_FRICTION;
+        let paddle_dir =
+            if      target - coast_x >  DEADBAND {  1 }
+            else if target - coast_x < -DEADBAND { -1 }
+            else                                 {  0 };
+
+        // let go of caught balls straight away, which takes a fresh press each time
+        let serve = !state.held.is_empty() && !state.serve_pressed;
+        Some(Input { paddle_dir, serve })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_autopilot_keeps_the_ball_up() {
+        let mut state = State::new(1234);
+        let mut autopilot = Autopilot::new(true);
+
+        for _ in 0 .. 20 * FRAMERATE {
+            let input = autopilot.input(&state).unwrap();
+            if state.update(DT, input) != Status::Playing {
+                break;
+            }
+        }
+
+        assert_eq!(state.lives, STARTING_LIVES);
+        assert!(state.scoring.score > 0);
+    }
+}
@@ -0,0 +1,331 @@
+
+use {
+    super::{
+        level::LoadError,
+        *,
+    },
+    std::{fmt, path::PathBuf},
+};
+
+#[derive(Clone, Debug)]
+pub enum LevelSource {
+    Procedural(u64),
+    File(PathBuf),
+    // a level file as it was read, so the level stays the same if the file changes
+    Text { path: PathBuf, text: String },
+}
+
+impl LevelSource {
+    // Command-line style: a number is a procedural seed, anything else a level file.
+    pub fn parse(arg: &str) -> LevelSource {
+        match arg.parse() {
+            Ok(seed) => LevelSource::Procedural(seed),
+            Err(_)   => LevelSource::File(arg.into()),
+        }
+    }
+
+    // A level file comes back as `Text`, keeping the level as it was read.
+    fn load(&self, rng: &mut Pcg32Basic) -> Result<(LevelSource, u64, Level), LoadError> {
+        let (source, seed, mut level) = match self {
+            LevelSource::Procedural(seed) => (self.clone(), *seed, Level::generate(*seed)),
+
+            LevelSource::File(path) => {
+                let text = std::fs::read_to_string(path).map_err(LoadError::Io)?;
+                let level = Level::parse(&text).map_err(LoadError::Parse)?;
+                (LevelSource::Text { path: path.clone(), text }, rng.next_u64(), level)
+            }
+
+            LevelSource::Text { text, .. } => {
+                (self.clone(), rng.next_u64(), Level::parse(text).map_err(LoadError::Parse)?)
+            }
+        };
+
+        if level.name.is_none() {
+            level.name = Some(self.to_string());
+        }
+
+        Ok((source, seed, level))
+    }
+}
+
+impl fmt::Display for LevelSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelSource::Procedural(seed) => write!(f, "seed {}", seed),
+            LevelSource::File(path)
+            | LevelSource::Text { path, .. } => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CampaignError {
+    NoLevels,
+    Level { level: LevelSource, error: LoadError },
+}
+
+impl fmt::Display for CampaignError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CampaignError::NoLevels               => write!(f, "a campaign needs at least one level"),
+            CampaignError::Level { level, error } => write!(f, "{}: {}", level, error),
+        }
+    }
+}
+
+impl std::error::Error for CampaignError { }
+
+#[derive(Clone, Debug)]
+pub struct LevelSummary {
+    pub name:    String,
+    pub scoring: Scoring,
+    pub rank:    Rank,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    Playing,
+    LevelCleared,
+    Finished,
+    GameOver,
+}
+
+impl Progress {
+    pub fn level_cleared(self) -> bool {
+        self == Progress::LevelCleared || self == Progress::Finished
+    }
+
+    pub fn is_over(self) -> bool {
+        self == Progress::Finished || self == Progress::GameOver
+    }
+}
+
+impl LevelSummary {
+    fn print(&self, number: usize, count: usize) {
+        println!("Level {}/{} cleared: {}", number, count, self.name);
+        println!("Score:     {:8}",  self.scoring.score);
+        println!("Bonus:     {:8}",  self.scoring.bonus);
+        println!("Penalties: {:8}",  self.scoring.penalties);
+        println!("Combo max: {:8}",  self.scoring.combo_max);
+        println!("Rank:      {:>8}", self.rank);
+    }
+}
+
+pub struct Campaign {
+    sources:   Vec<LevelSource>,
+    levels:    Vec<(u64, Level)>,
+    current:   usize,
+    state:     State,
+    start:     Scoring,
+    summaries: Vec<LevelSummary>,
+}
+
+impl Campaign {
+    // Level files are read once, up front.
+    pub fn new(seed: u64, sources: &[LevelSource]) -> Result<Campaign, CampaignError> {
+        if sources.is_empty() {
+            return Err(CampaignError::NoLevels);
+        }
+
+        let mut rng = Pcg32Basic::seed_from_u64(seed);
+        let loaded: Vec<_> = sources.iter()
+            .map(|source| source.load(&mut rng)
+                .map_err(|error| CampaignError::Level { level: source.clone(), error }))
+            .collect::<Result<_, _>>()?;
+
+        let (sources, levels): (Vec<_>, Vec<_>) = loaded.into_iter()
+            .map(|(source, seed, level)| (source, (seed, level)))
+            .unzip();
+
+        let (seed, level) = levels[0].clone();
+        let state = State::from_level(seed, level);
+        let start = state.scoring;
+
+        Ok(Campaign {
+            sources,
+            levels,
+            current: 0,
+            state,
+            start,
+            summaries: Vec::new(),
+        })
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    // The levels being played, with the text of any level files.
+    pub fn sources(&self) -> &[LevelSource] {
+        &self.sources
+    }
+
+    pub fn rect(&self) -> Rect {
+        self.state.rect()
+    }
+
+    pub fn events(&self) -> &[Event] {
+        self.state.events()
+    }
+
+    pub fn frame<'a> (&'a self, alpha: f32) -> Frame<'a> {
+        self.state.frame(alpha)
+    }
+
+    pub fn summaries(&self) -> &[LevelSummary] {
+        &self.summaries
+    }
+
+    pub fn lives(&self) -> i32 {
+        self.state.lives
+    }
+
+    pub fn totals(&self) -> Scoring {
+        let mut scoring = self.state.scoring;
+        scoring.combo_max = self.summaries.iter()
+            .map(|summary| summary.scoring.combo_max)
+            .chain(std::iter::once(scoring.combo_max))
+            .max()
+            .unwrap_or(0);
+        scoring
+    }
+
+    fn summarize_level(&mut self) {
+        let (_, level) = &self.levels[self.current];
+        let scoring = self.state.scoring.since(&self.start);
+        let summary = LevelSummary {
+            name: level.name.clone().unwrap_or_default(),
+            scoring,
+            rank: scoring.rank(),
+        };
+
+        self.summaries.push(summary);
+    }
+
+    pub fn print_level_summary(&self) {
+        if let Some(summary) = self.summaries.last() {
+            summary.print(self.summaries.len(), self.levels.len());
+        }
+    }
+
+    pub fn print_summary(&self) {
+        let headline =
+            if self.summaries.len() == self.levels.len() { "A winner is you!" }
+            else                                         { "Game over!" };
+
+        let scoring = self.totals();
+
+        println!("{}", headline);
+        println!("Levels:    {:>8}", format!("{}/{}", self.summaries.len(), self.levels.len()));
+        println!("Score:     {:8}",  scoring.score);
+        println!("Bonus:     {:8}",  scoring.bonus);
+        println!("Penalties: {:8}",  scoring.penalties);
+        println!("Combo max: {:8}",  scoring.combo_max);
+        println!("Rank:      {:>8}", scoring.rank());
+    }
+
+    pub fn update(&mut self, dt: f32, input: Input) -> Progress {
+        match self.state.update(dt, input) {
+            Status::Playing  => Progress::Playing,
+            Status::GameOver => Progress::GameOver,
+
+            Status::Cleared => {
+                self.summarize_level();
+                self.current += 1;
+
+                if self.current == self.levels.len() {
+                    return Progress::Finished;
+                }
+
+                let (seed, level) = self.levels[self.current].clone();
+                let mut state = State::from_level(seed, level);
+                state.carry_over(&mut self.state);
+
+                self.start = state.scoring;
+                self.state = state;
+                Progress::LevelCleared
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn campaign(levels: &[&str]) -> Campaign {
+        let sources: Vec<_> = levels.iter()
+            .enumerate()
+            .map(|(index, text)| LevelSource::Text {
+                path: format!("level {}", index).into(),
+                text: text.to_string(),
+            })
+            .collect();
+        Campaign::new(0, &sources).unwrap()
+    }
+
+    // Drops a ball through the floor, well away from the paddle.
+    fn lose_ball(campaign: &mut Campaign) -> Progress {
+        campaign.state.balls.push(FlyingBall::new(P2::new(-200., 100.), V2::new(0., -400.)));
+        loop {
+            let progress = campaign.update(DT, Input { paddle_dir: 0, serve: false });
+            if campaign.state.balls.is_empty() {
+                return progress;
+            }
+        }
+    }
+
+    // Breaks each block in turn, banking after each.
+    fn break_blocks(campaign: &mut Campaign) {
+        let ids: Vec<BlockId> = campaign.state.blocks.iter().map(|(id, _)| id).collect();
+        for id in ids {
+            campaign.state.hit_block(id, P2::origin(), 0.);
+            campaign.state.scoring.hit_paddle();
+        }
+    }
+
+    #[test]
+    fn test_lives_and_score_carry_over() {
+        let mut campaign = campaign(&[
+            "row 4:score:hp=1:score=50",
+            "row 4:score:hp=1:score=30 4:score:hp=1:score=30",
+        ]);
+
+        assert_eq!(lose_ball(&mut campaign), Progress::Playing);
+        break_blocks(&mut campaign);
+        assert_eq!(campaign.update(DT, Input { paddle_dir: 0, serve: false }), Progress::LevelCleared);
+
+        assert_eq!(campaign.lives(), STARTING_LIVES - 1);
+        assert_eq!(campaign.state.scoring.score, 50);
+        assert_eq!(campaign.state.scoring.combo_max, 0);
+
+        break_blocks(&mut campaign);
+        assert_eq!(campaign.update(DT, Input { paddle_dir: 0, serve: false }), Progress::Finished);
+
+        // each summary only has its own level's numbers
+        let summaries = campaign.summaries();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!((summaries[0].scoring.score, summaries[0].scoring.combo_max), (50, 50));
+        assert_eq!((summaries[1].scoring.score, summaries[1].scoring.combo_max), (60, 30));
+        assert_eq!(summaries[0].rank, Rank::S);
+
+        let totals = campaign.totals();
+        assert_eq!((totals.score, totals.combo_max), (110, 50));
+    }
+
+    #[test]
+    fn test_a_campaign_needs_levels() {
+        assert!(matches!(Campaign::new(0, &[]), Err(CampaignError::NoLevels)));
+    }
+
+    #[test]
+    fn test_game_over_ends_the_campaign() {
+        let mut campaign = campaign(&["row 4:score:hp=1", "row 4:score:hp=1"]);
+        for _ in 1 .. STARTING_LIVES {
+            assert_eq!(lose_ball(&mut campaign), Progress::Playing);
+        }
+
+        assert_eq!(lose_ball(&mut campaign), Progress::GameOver);
+        assert!(campaign.summaries().is_empty());
+    }
+}
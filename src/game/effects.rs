@@ -0,0 +1,129 @@
+
+const MAX_STACKS: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EffectKind {
+    WidePaddle,
+    NarrowPaddle,
+    // the paddle holds on to balls that land on it, until the next serve
+    Catch,
+}
+
+impl EffectKind {
+    pub fn duration(self) -> f32 {
+        match self {
+            EffectKind::WidePaddle   => 15.,
+            EffectKind::NarrowPaddle => 10.,
+            EffectKind::Catch        => 20.,
+        }
+    }
+
+    // The effect that this one wears off a stack of, rather than going on alongside.
+    fn opposite(self) -> Option<EffectKind> {
+        match self {
+            EffectKind::WidePaddle   => Some(EffectKind::NarrowPaddle),
+            EffectKind::NarrowPaddle => Some(EffectKind::WidePaddle),
+            EffectKind::Catch        => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Effect {
+    pub kind:      EffectKind,
+    pub stacks:    u32,
+    pub remaining: f32,
+}
+
+// Effects that last for a while. Starting one that's already going adds a stack, up
+// to `MAX_STACKS`, and starts its time over; starting one whose opposite is going
+// takes a stack off that instead.
+#[derive(Clone, Debug, Default)]
+pub struct Effects {
+    active: Vec<Effect>,
+}
+
+impl<'a> IntoIterator for &'a Effects {
+    type Item = &'a Effect;
+    type IntoIter = std::slice::Iter<'a, Effect>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.active.iter()
+    }
+}
+
+impl Effects {
+    pub fn new() -> Effects {
+        Effects::default()
+    }
+
+    pub fn is_active(&self, kind: EffectKind) -> bool {
+        self.stacks(kind) > 0
+    }
+
+    pub fn stacks(&self, kind: EffectKind) -> u32 {
+        self.active.iter()
+            .find(|effect| effect.kind == kind)
+            .map_or(0, |effect| effect.stacks)
+    }
+
+    pub fn start(&mut self, kind: EffectKind) {
+        let opposite = kind.opposite();
+        if let Some(effect) = self.active.iter_mut().find(|effect| Some(effect.kind) == opposite) {
+            effect.stacks -= 1;
+            self.active.retain(|effect| effect.stacks > 0);
+            return;
+        }
+
+        match self.active.iter_mut().find(|effect| effect.kind == kind) {
+            Some(effect) => {
+                effect.stacks = (effect.stacks + 1).min(MAX_STACKS);
+                effect.remaining = kind.duration();
+            }
+
+            None => {
+                self.active.push(Effect { kind, stacks: 1, remaining: kind.duration() });
+            }
+        }
+    }
+
+    // Returns the effects that ran out.
+    pub fn update(&mut self, dt: f32) -> Vec<EffectKind> {
+        for effect in self.active.iter_mut() {
+            effect.remaining -= dt;
+        }
+
+        let expired = self.active.iter()
+            .filter(|effect| effect.remaining <= 0.)
+            .map(|effect| effect.kind)
+            .collect();
+        self.active.retain(|effect| effect.remaining > 0.);
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stacking_and_expiry() {
+        use EffectKind::*;
+        let mut effects = Effects::new();
+
+        for _ in 0 .. 5 {
+            effects.start(WidePaddle);
+        }
+        assert_eq!(effects.stacks(WidePaddle), MAX_STACKS);
+
+        // the opposite takes a stack away rather than starting
+        effects.start(NarrowPaddle);
+        assert_eq!(effects.stacks(WidePaddle), MAX_STACKS - 1);
+        assert_eq!(effects.stacks(NarrowPaddle), 0);
+
+        assert!(effects.update(WidePaddle.duration() - 1.).is_empty());
+        effects.start(WidePaddle);
+        assert!(effects.update(2.).is_empty());
+        assert_eq!(effects.update(WidePaddle.duration()), [WidePaddle]);
+        assert_eq!(effects.stacks(WidePaddle), 0);
+    }
+}
@@ -0,0 +1,32 @@
+
+use {
+    super::*,
+};
+
+// Something that happened during a tick, for whoever is drawing, playing sounds or
+// keeping stats. `State::events` holds those from the latest update.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    BallServed      { position: P2 },
+    // held on the paddle by the catch effect, until it's served again
+    BallCaught      { position: P2 },
+    PaddleHit       { position: P2 },
+    // `normal` points back into the playfield
+    WallHit         { position: P2, normal: V2 },
+    // `kind` is the block as the hit found it, and `hp` what the hit left it with
+    BlockDamaged    { id: BlockId, hp: i32, position: P2, kind: block::Kind },
+    BlockBroken     { id: BlockId, score: i64, rect: Rect, kind: block::Kind },
+    // follows the `BlockBroken` for an explosive block, ahead of whatever it sets off
+    BlockExploded   { id: BlockId, rect: Rect },
+    BlockHealed     { id: BlockId, hp: i32 },
+    // each gate a switch opened or closed
+    GateToggled     { id: BlockId, open: bool },
+    PickupSpawned   { kind: PickupKind, position: P2 },
+    PickupCollected { kind: PickupKind },
+    EffectEnded     { kind: EffectKind },
+    // `life_lost` once the last ball in play has gone
+    FloorHit        { position: P2, life_lost: bool },
+    // `value` went into the score, or came out of it as a penalty if `forfeit`
+    ComboEnded      { value: i64, forfeit: bool },
+    LevelCleared,
+}
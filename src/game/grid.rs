@@ -0,0 +1,159 @@
+
+use {
+    super::*,
+    std::ops::RangeInclusive,
+};
+
+const COLUMNS: i32 = GAME_WIDTH  / SPLIT_STEP;
+const ROWS:    i32 = GAME_HEIGHT / BLOCK_H;
+
+// Uniform grid on the block lattice, listing the blocks that overlap each cell.
+// Blocks only ever disappear, so it's built once and kept in step with removals.
+// Moving blocks are listed everywhere their patrol takes them.
+pub struct Grid {
+    cells: Vec<Vec<BlockId>>,
+}
+
+impl Grid {
+    pub fn new(blocks: &Arena<Block>) -> Grid {
+        let mut grid = Grid { cells: vec![Vec::new(); (COLUMNS * ROWS) as usize] };
+        for (id, block) in blocks.iter() {
+            for cell in Grid::cells_under(block.bounds(), false) {
+                grid.cells[cell].push(id);
+            }
+        }
+        grid
+    }
+
+    fn span(low: f32, high: f32, closed: bool, origin: i32, step: i32, count: i32)
+        -> RangeInclusive<i32>
+    {
+        let low  = (low  - origin as f32) / step as f32;
+        let high = (high - origin as f32) / step as f32;
+        let (low, high) =
+            if closed { (low.ceil() - 1., high.floor()) }
+            else      { (low.floor(), (high.ceil() - 1.).max(low.floor())) };
+        (low as i32).clamp(0, count - 1) ..= (high as i32).clamp(0, count - 1)
+    }
+
+    // The cells `rect` overlaps, including those it only touches along its edges
+    // if `closed`. Anything off the grid counts as being in the nearest cell.
+    fn cells_under(rect: Rect, closed: bool) -> impl Iterator<Item = usize> {
+        let columns = Grid::span(rect.mins.x, rect.maxs.x, closed, GAME_LEFT,   SPLIT_STEP, COLUMNS);
+        let rows    = Grid::span(rect.mins.y, rect.maxs.y, closed, GAME_BOTTOM, BLOCK_H,    ROWS);
+        rows.flat_map(move |row| columns.clone().map(move |column| (row * COLUMNS + column) as usize))
+    }
+
+    pub fn remove(&mut self, id: BlockId, rect: Rect) {
+        for cell in Grid::cells_under(rect, false) {
+            self.cells[cell].retain(|other| *other != id);
+        }
+    }
+
+    // The blocks in the cells overlapping `area`, each once, in order.
+    pub fn query(&self, area: Rect) -> Vec<BlockId> {
+        let mut found: Vec<BlockId> = Grid::cells_under(area, true)
+            .flat_map(|cell| self.cells[cell].iter().copied())
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    // The blocks a circle of `radius` might run into along `motion`, each once, in
+    // order, into `found`. Only the columns the motion reaches within each row it
+    // crosses are looked in, not everything under its bounding box.
+    pub fn query_motion(&self, motion: Segment, radius: f32, found: &mut Vec<BlockId>) {
+        found.clear();
+
+        let (from, stride) = (motion.source(), motion.stride());
+        let to = motion.destination();
+        let rows = Grid::span(from.y.min(to.y) - radius, from.y.max(to.y) + radius, true, GAME_BOTTOM, BLOCK_H, ROWS);
+        for row in rows {
+            // the stretch of the motion within reach of the row; the rows along the
+            // edges reach on past the grid
+            let low  = if row == 0        { f32::NEG_INFINITY }
+                       else               { (GAME_BOTTOM + row * BLOCK_H) as f32 - radius };
+            let high = if row == ROWS - 1 { f32::INFINITY }
+                       else               { (GAME_BOTTOM + (row + 1) * BLOCK_H) as f32 + radius };
+
+            let (enter, leave) =
+                if stride.y == 0. { (0., 1.) }
+                else {
+                    let (a, b) = ((low - from.y) / stride.y, (high - from.y) / stride.y);
+                    (a.min(b).max(0.), a.max(b).min(1.))
+                };
+
+            if enter > leave {
+                continue;
+            }
+
+            let (x0, x1) = (from.x + stride.x * enter, from.x + stride.x * leave);
+            let columns = Grid::span(x0.min(x1) - radius, x0.max(x1) + radius, true, GAME_LEFT, SPLIT_STEP, COLUMNS);
+            for column in columns {
+                found.extend_from_slice(&self.cells[(row * COLUMNS + column) as usize]);
+            }
+        }
+
+        found.sort_unstable();
+        found.dedup();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_and_remove() {
+        let level = Level::parse("
+            row 4:score 4:score 8:score
+            row 4:gap 4:score
+        ").unwrap();
+
+        let mut blocks: Arena<Block> = level.blocks.into_iter().collect();
+        let mut grid = Grid::new(&blocks);
+        let ids: Vec<BlockId> = blocks.iter().map(|(id, _)| id).collect();
+
+        // touching the second block of the first row from beneath
+        let below = blocks[ids[1]].rect.mins + V2::new(2., -1.);
+        let area = Rect::new_square_centered(2.).at(below);
+        assert_eq!(grid.query(area), vec![ids[1], ids[3]]);
+
+        let far = Rect::new_square_centered(2.).at(P2::new(0., 10.));
+        assert!(grid.query(far).is_empty());
+
+        let removed = blocks.remove(ids[1]).unwrap();
+        grid.remove(ids[1], removed.rect);
+        assert_eq!(grid.query(area), vec![ids[3]]);
+
+        let next = blocks[ids[2]].rect.contract(1.);
+        assert_eq!(grid.query(next), vec![ids[2]]);
+    }
+
+    #[test]
+    fn test_motion_only_finds_the_cells_it_crosses() {
+        let level = Level::parse("
+            row 1:score 1:gap 1:score
+            row 1:score 1:gap 1:score
+        ").unwrap();
+
+        let blocks: Arena<Block> = level.blocks.into_iter().collect();
+        let grid = Grid::new(&blocks);
+        let ids: Vec<BlockId> = blocks.iter().map(|(id, _)| id).collect();
+
+        // up and across from the bottom left block to the top right, missing the
+        // other two in the corners of its bounding box
+        let from = blocks[ids[2]].rect.mins + V2::new(8., 5.);
+        let to   = blocks[ids[1]].rect.mins + V2::new(8., 25.);
+        let motion = Segment::new_from_points(from, to);
+        assert_eq!(grid.query(Rect::new(from, to).expand(1.)).len(), 4);
+
+        let mut found = vec![ids[0]];
+        grid.query_motion(motion, 1., &mut found);
+        assert_eq!(found, vec![ids[1], ids[2]]);
+
+        grid.query_motion(Segment::new(from, V2::zeros()), 1., &mut found);
+        assert_eq!(found, vec![ids[2]]);
+    }
+}
@@ -0,0 +1,552 @@
+
+use {
+    super::*,
+    rand::Rng,
+    std::{fmt, path::Path},
+};
+
+// Levels are plain text, one directive per line. Blank lines and anything after
+// a '#' are ignored.
+//
+//   name <text>         optional display name
+//   speed <param>...    the ball's speed curve, as `<param>=<value>` pairs
+//   bonus combo|bank    whether bonuses ride on the combo (the default) or bank at once
+//   row <block>...      the next row of blocks, from the top of the field down
+//
+// The speed curve starts the ball off at `base` and speeds it up by `hit` for
+// each paddle hit, `block` for each block broken and `time` every second, up to
+// `max`. Losing a life keeps the `keep` fraction of the speed gained so far.
+//
+// A block is written as `<width>:<kind>[:<param>=<value>]...`, where the width
+// is measured in split steps and a row may be at most `SPLITS_PER_ROW` wide.
+// Any space left at the right end of a row is empty.
+//
+//   score       breakable block; `hp` defaults to the width, `score` to 10 per step
+//   explosive   breakable, and hits its neighbours when it breaks
+//   armored     breakable, but only by a ball going at least `speed`
+//   regen       breakable, but heals a point every `delay` seconds after a hit
+//   switch      invulnerable, and opens or closes the gates with the same `link`
+//   gate        invulnerable while closed, or while `open=1` to begin with
+//   solid       invulnerable block
+//   gap         empty space
+//
+// The special breakable blocks take `hp` and `score` as well. Links are numbered
+// from 0, the default, and don't need to be breakable for the level to be cleared.
+//
+// Any block but a gap can patrol. `path=<dx>,<dy>/...` sends it through a series of
+// offsets from its starting place and back again, and `loop=` does the same but
+// goes straight back home from the last. Offsets are in split steps across and
+// rows down, and the block moves at `pace` pixels per second. The whole patrol must
+// stay between the walls.
+//
+// For example:
+//
+//   name Stripes
+//   speed base=380 max=650 hit=5
+//   bonus bank
+//   row 4:score 4:score:hp=2 8:solid 4:gap 4:score:hp=3:score=80
+//   row 4:explosive:hp=1 8:armored:speed=500 4:regen:delay=2.5
+//   row 4:score:path=12,0:pace=80 8:gap 4:solid:loop=0,1/-4,1/-4,0
+//   row 2:switch:link=1 36:gate:link=1 2:switch:link=1
+
+const SPLITS_PER_ROW: i32 = GAME_WIDTH / SPLIT_STEP;
+const MAX_ROWS:       i32 = GAME_HEIGHT / BLOCK_H - 6;
+
+// Gives the generator's choice of block kinds a stream of its own, so the layout
+// for a seed stays as it was.
+const KINDS_SEED_MIX: u64 = 0x2545_f491_4f6c_dd1d;
+
+// Chances of a generated breakable block being each of the special kinds.
+const EXPLOSIVE_CHANCE: f32 = 0.06;
+const ARMORED_CHANCE:   f32 = 0.05;
+const REGEN_CHANCE:     f32 = 0.05;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpeedCurve {
+    pub base:  f32,
+    pub max:   f32,
+    pub hit:   f32,
+    pub block: f32,
+    pub time:  f32,
+    pub keep:  f32,
+}
+
+impl Default for SpeedCurve {
+    fn default() -> SpeedCurve {
+        SpeedCurve {
+            base:  BALL_SERVE_SPEED,
+            max:   BALL_SERVE_SPEED * 1.6,
+            hit:   4.,
+            block: 2.,
+            time:  1.,
+            keep:  0.5,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub name:       Option<String>,
+    pub speed:      SpeedCurve,
+    pub bonus_mode: BonusMode,
+    pub blocks:     Vec<Block>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub line:    usize,
+    pub column:  usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError { }
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(err)    => err.fmt(f),
+            LoadError::Parse(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for LoadError { }
+
+fn block_rect(row: i32, left: i32, right: i32) -> Rect {
+    let y0 = (GAME_TOP - (row + 2) * BLOCK_H) as f32;
+    let y1 = y0 + BLOCK_H as f32;
+    let x0 = GAME_LEFT as f32 + (left  * SPLIT_STEP) as f32;
+    let x1 = GAME_LEFT as f32 + (right * SPLIT_STEP) as f32;
+    Rect::new(P2::new(x0, y0), P2::new(x1, y1))
+}
+
+// Splits `text` on `sep`, pairing each non-empty piece with its byte offset.
+pub(super) fn pieces(text: &str, offset: usize, sep: impl Fn(char) -> bool) -> Vec<(usize, &str)> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (index, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        if index == text.len() || sep(c) {
+            if index > start {
+                pieces.push((offset + start, &text[start .. index]));
+            }
+            start = index + c.len_utf8();
+        }
+    }
+    pieces
+}
+
+struct LineParser {
+    line: usize,
+}
+
+impl LineParser {
+    fn error<T>(&self, offset: usize, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError { line: self.line, column: offset + 1, message: message.into() })
+    }
+
+    fn number(&self, offset: usize, text: &str, what: &str, min: i32) -> Result<i32, ParseError> {
+        self.number_within(offset, text, what, min, i32::MAX)
+    }
+
+    fn number_within(&self, offset: usize, text: &str, what: &str, min: i32, max: i32)
+        -> Result<i32, ParseError>
+    {
+        match text.parse::<i32>() {
+            Ok(value) if (min ..= max).contains(&value) => Ok(value),
+            Ok(_) if max == i32::MAX => self.error(offset, format!("{} must be at least {}", what, min)),
+            Ok(_)  => self.error(offset, format!("{} must be from {} to {}", what, min, max)),
+            Err(_) => self.error(offset, format!("expected a number for {}, found `{}`", what, text)),
+        }
+    }
+
+    fn float(&self, offset: usize, text: &str, what: &str) -> Result<f32, ParseError> {
+        match text.parse::<f32>() {
+            Ok(value) if value >= 0. && value.is_finite() => Ok(value),
+            Ok(_)  => self.error(offset, format!("{} must not be negative", what)),
+            Err(_) => self.error(offset, format!("expected a number for {}, found `{}`", what, text)),
+        }
+    }
+
+    // A patrol's offsets, as `<dx>,<dy>` pairs separated by slashes.
+    fn offsets(&self, offset: usize, text: &str) -> Result<Vec<V2>, ParseError> {
+        let mut offsets = Vec::new();
+        for (point_offset, point) in pieces(text, offset, |c| c == '/') {
+            let coords = pieces(point, point_offset, |c| c == ',');
+            if coords.len() != 2 {
+                return self.error(point_offset, format!("expected `<dx>,<dy>`, found `{}`", point));
+            }
+
+            let dx = self.number_within(coords[0].0, coords[0].1, "dx", -SPLITS_PER_ROW, SPLITS_PER_ROW)?;
+            let dy = self.number_within(coords[1].0, coords[1].1, "dy", -MAX_ROWS, MAX_ROWS)?;
+            offsets.push(V2::new((dx * SPLIT_STEP) as f32, -(dy * BLOCK_H) as f32));
+        }
+
+        if offsets.is_empty() {
+            return self.error(offset, "expected at least one `<dx>,<dy>` offset");
+        }
+
+        Ok(offsets)
+    }
+
+    fn speed(&self, words: &[(usize, &str)], speed: &mut SpeedCurve) -> Result<(), ParseError> {
+        for &(offset, param) in words {
+            let (key, value) = match param.find('=') {
+                Some(split) => (&param[.. split], &param[split + 1 ..]),
+                None => { return self.error(offset, format!("expected `<param>=<value>`, found `{}`", param)); }
+            };
+
+            let value = self.float(offset + key.len() + 1, value, key)?;
+            match key {
+                "base"  => { speed.base  = value; }
+                "max"   => { speed.max   = value; }
+                "hit"   => { speed.hit   = value; }
+                "block" => { speed.block = value; }
+                "time"  => { speed.time  = value; }
+                "keep" if value <= 1. => { speed.keep = value; }
+                "keep"  => { return self.error(offset, "keep must be at most 1"); }
+                _ => { return self.error(offset, format!("unknown speed parameter `{}`", key)); }
+            }
+        }
+
+        if speed.base <= 0. || speed.max < speed.base {
+            let offset = words.first().map_or(0, |word| word.0);
+            return self.error(offset, "speed needs 0 < base <= max");
+        }
+
+        Ok(())
+    }
+
+    fn block(&self, offset: usize, token: &str, row: i32, left: i32)
+        -> Result<(i32, Option<Block>), ParseError>
+    {
+        let parts = pieces(token, offset, |c| c == ':');
+        let (width_offset, width) = match parts.first() {
+            Some(part) => *part,
+            None => { return self.error(offset, "expected `<width>:<kind>`"); }
+        };
+        let width = self.number(width_offset, width, "block width", 1)?;
+
+        let (kind_offset, kind) = match parts.get(1) {
+            Some(part) => *part,
+            None => { return self.error(offset + token.len(), "expected `:<kind>` after the block width"); }
+        };
+
+        let right = match left.checked_add(width) {
+            Some(right) if right <= SPLITS_PER_ROW => right,
+            _ => { return self.error(width_offset, format!("row is wider than {} steps", SPLITS_PER_ROW)); }
+        };
+
+        let breakable = match kind {
+            "score" | "explosive" | "armored" | "regen" => true,
+            "switch" | "gate" | "solid" | "gap" => false,
+            _ => { return self.error(kind_offset, format!("unknown block kind `{}`", kind)); }
+        };
+
+        let mut hp        = width;
+        let mut score     = width * 10;
+        let mut min_speed = ARMOR_SPEED;
+        let mut delay     = REGEN_DELAY;
+        let mut offsets   = Vec::new();
+        let mut looped    = false;
+        let mut path_offset = 0;
+        let mut pace      = PATROL_PACE;
+        let mut link      = 0;
+        let mut open      = false;
+
+        for &(param_offset, param) in &parts[2..] {
+            let (key, value) = match param.find('=') {
+                Some(split) => (&param[.. split], &param[split + 1 ..]),
+                None => { return self.error(param_offset, format!("expected `<param>=<value>`, found `{}`", param)); }
+            };
+
+            let value_offset = param_offset + key.len() + 1;
+            match (kind, key) {
+                (_, "hp")    if breakable => { hp        = self.number(value_offset, value, "hp", 1)?; }
+                (_, "score") if breakable => { score     = self.number(value_offset, value, "score", 0)?; }
+                ("armored", "speed")      => { min_speed = self.float(value_offset, value, "speed")?; }
+                ("regen",   "delay")      => { delay     = self.float(value_offset, value, "delay")?; }
+                (_, "path") | (_, "loop") if kind != "gap" => {
+                    offsets = self.offsets(value_offset, value)?;
+                    looped = key == "loop";
+                    path_offset = param_offset;
+                }
+                (_, "pace") if kind != "gap" => { pace = self.float(value_offset, value, "pace")?; }
+                ("switch", "link") | ("gate", "link") => { link = self.number(value_offset, value, "link", 0)? as u32; }
+                ("gate", "open") => { open = self.number(value_offset, value, "open", 0)? != 0; }
+                _ => { return self.error(param_offset, format!("`{}` blocks have no `{}` parameter", kind, key)); }
+            }
+        }
+
+        use block::Kind::*;
+        let rect = block_rect(row, left, right);
+        let kind = match kind {
+            "score"     => Scoring { score, hp },
+            "explosive" => Explosive { score, hp },
+            "armored"   => Armored { score, hp, min_speed },
+            "regen"     => block::Kind::regenerating(score, hp, delay),
+            "switch"    => Switch { link },
+            "gate"      => Gate { link, open },
+            "solid"     => Invlunerable,
+            _           => { return Ok((width, None)); } // gap
+        };
+
+        let patrol =
+            if offsets.is_empty() { None }
+            else                  { Some(Patrol::new(rect, &offsets, pace, looped)) };
+
+        let block = Block { kind, rect, patrol };
+        if block.bounds().union(&walls_rect()) != walls_rect() {
+            return self.error(path_offset, "patrol goes outside the walls");
+        }
+
+        Ok((width, Some(block)))
+    }
+}
+
+impl Level {
+    pub fn parse(text: &str) -> Result<Level, ParseError> {
+        let mut name       = None;
+        let mut speed      = SpeedCurve::default();
+        let mut bonus_mode = BONUS_MODE;
+        let mut blocks     = Vec::new();
+        let mut row        = 0;
+
+        for (index, line) in text.lines().enumerate() {
+            let parser = LineParser { line: index + 1 };
+            let line = match line.find('#') {
+                Some(comment) => &line[.. comment],
+                None          => line,
+            };
+
+            let words = pieces(line, 0, char::is_whitespace);
+            let (offset, directive) = match words.first() {
+                Some(word) => *word,
+                None       => { continue; }
+            };
+
+            match directive {
+                "name" => {
+                    let rest = line[offset + directive.len() ..].trim();
+                    if rest.is_empty() {
+                        return parser.error(offset, "expected a level name");
+                    }
+                    name = Some(rest.to_owned());
+                }
+
+                "speed" => {
+                    parser.speed(&words[1..], &mut speed)?;
+                }
+
+                "bonus" => {
+                    bonus_mode = match &words[1..] {
+                        [(_, "combo")] => BonusMode::Combo,
+                        [(_, "bank")]  => BonusMode::Bank,
+                        _ => { return parser.error(offset, "expected `bonus combo` or `bonus bank`"); }
+                    };
+                }
+
+                "row" => {
+                    if row == MAX_ROWS {
+                        return parser.error(offset, format!("a level has at most {} rows", MAX_ROWS));
+                    }
+
+                    let mut left = 0;
+                    for &(offset, token) in &words[1..] {
+                        let (width, block) = parser.block(offset, token, row, left)?;
+                        blocks.extend(block);
+                        left += width;
+                    }
+
+                    row += 1;
+                }
+
+                _ => { return parser.error(offset, format!("unknown directive `{}`", directive)); }
+            }
+        }
+
+        Ok(Level { name, speed, bonus_mode, blocks })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Level, LoadError> {
+        let text = std::fs::read_to_string(path).map_err(LoadError::Io)?;
+        Level::parse(&text).map_err(LoadError::Parse)
+    }
+
+    pub fn generate(seed: u64) -> Level {
+        let mut rand = Pcg32Basic::seed_from_u64(seed);
+        let next_rand = Pcg32Basic::seed_from_u64(rand.next_u64());
+        let mut kinds_rand = Pcg32Basic::seed_from_u64(seed ^ KINDS_SEED_MIX);
+        let mut split_distro = Bernoulli::new(0.3)
+            .unwrap()
+            .sample_iter(rand);
+
+        let splits: Vec<Vec<_>> = (1..BLOCKS_VERT)
+            .map(|_| {
+                let splits = (1 .. SPLITS_PER_ROW)
+                    .zip(split_distro.by_ref())
+                    .filter(|(_, keep)| *keep)
+                    .map(|(i, _)| i);
+                let right_end = std::iter::once(SPLITS_PER_ROW);
+                splits.chain(right_end).collect()
+            })
+            .collect();
+
+        let block_keep_distro = Bernoulli::new(0.95)
+            .unwrap()
+            .sample_iter(next_rand);
+
+        let blocks = splits.iter()
+            .enumerate()
+            .flat_map(|(y_index, splits)| {
+                splits.iter()
+                    .scan(0, move |l, r| {
+                        assert!(*r > *l);
+
+                        let rect = block_rect(y_index as i32, *l, *r);
+                        let w = *r - *l;
+
+                        use block::Kind::*;
+                        let block = if w > 8 {
+                            Block { kind: Invlunerable, rect: rect.contract(8.), patrol: None }
+                        }
+                        else {
+                            Block { kind: Scoring { score: w * 10, hp: w }, rect, patrol: None }
+                        };
+
+                        assert!(rect.width() > 0.);
+
+                        *l = *r;
+                        Some(block)
+                    })
+            })
+            .zip(block_keep_distro)
+            .filter(|(_, keep)| *keep)
+            .map(|(block, _)| block)
+            .map(|block| Level::vary(&mut kinds_rand, block))
+            .collect();
+
+        Level { name: None, speed: SpeedCurve::default(), bonus_mode: BONUS_MODE, blocks }
+    }
+
+    // Turns some plain scoring blocks into the special kinds.
+    fn vary(rand: &mut Pcg32Basic, mut block: Block) -> Block {
+        use block::Kind::*;
+        if let Scoring { score, hp } = block.kind {
+            let roll = rand.gen::<f32>();
+            block.kind =
+                if roll < EXPLOSIVE_CHANCE {
+                    Explosive { score, hp: 1 }
+                }
+                else if roll < EXPLOSIVE_CHANCE + ARMORED_CHANCE {
+                    Armored { score: score * 2, hp, min_speed: ARMOR_SPEED }
+                }
+                else if roll < EXPLOSIVE_CHANCE + ARMORED_CHANCE + REGEN_CHANCE {
+                    block::Kind::regenerating(score, hp, REGEN_DELAY)
+                }
+                else {
+                    Scoring { score, hp }
+                };
+        }
+
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rows() {
+        let level = Level::parse("
+            name Test   # trailing comment
+            speed base=300 max=500 keep=0.25
+            bonus bank
+            row 4:score 4:score:hp=2:score=55 8:solid
+            row 10:gap 2:score
+        ").unwrap();
+
+        assert_eq!(level.name.as_deref(), Some("Test"));
+        assert_eq!(level.bonus_mode, BonusMode::Bank);
+        assert_eq!(level.speed.base, 300.);
+        assert_eq!(level.speed.keep, 0.25);
+        assert_eq!(level.speed.hit, SpeedCurve::default().hit);
+        assert_eq!(level.blocks.len(), 4);
+        assert_eq!(level.blocks[0].hp(), Some(4));
+        assert_eq!(level.blocks[1].hp(), Some(2));
+        assert!(!level.blocks[2].is_scoring());
+
+        let last = &level.blocks[3];
+        assert_eq!(last.hp(), Some(2));
+        assert_eq!(last.rect.mins.x, (GAME_LEFT + 10 * SPLIT_STEP) as f32);
+        assert_eq!(last.rect.maxs.y, (GAME_TOP - 2 * BLOCK_H) as f32);
+    }
+
+    #[test]
+    fn test_parse_special_blocks() {
+        let level = Level::parse("row 4:explosive:hp=1 8:armored:speed=500 4:regen:delay=2.5:score=5").unwrap();
+        assert!(matches!(level.blocks[0].kind, block::Kind::Explosive { hp: 1, score: 40 }));
+        assert!(matches!(level.blocks[1].kind, block::Kind::Armored { hp: 8, .. }));
+        assert!(matches!(level.blocks[2].kind, block::Kind::Regenerating { score: 5, max_hp: 4, .. }));
+
+        let level = Level::parse("row 4:gap 4:solid:loop=0,1/-4,1/-4,0:pace=30").unwrap();
+        assert_eq!(level.blocks[0].bounds(), Rect::new(block_rect(1, 0, 4).mins, block_rect(0, 4, 8).maxs));
+
+        let level = Level::parse("row 2:switch:link=3 4:gate:link=3:open=1 2:gate").unwrap();
+        assert!(matches!(level.blocks[0].kind, block::Kind::Switch { link: 3 }));
+        assert!(matches!(level.blocks[1].kind, block::Kind::Gate { link: 3, open: true }));
+        assert!(matches!(level.blocks[2].kind, block::Kind::Gate { link: 0, open: false }));
+
+        let err = Level::parse("row 4:score:speed=500").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 13));
+
+        let err = Level::parse("row 4:score:path=2147483647,0").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 18));
+
+        let err = Level::parse("row 4:solid:pace=10:loop=0,1/0,-2").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 21));
+
+        let err = Level::parse("row 30:gap 4:score:path=-8,0/8,1").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 20));
+        assert!(Level::parse("row 30:gap 4:score:path=-8,0/6,1").is_ok());
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let err = Level::parse("row 4:score\nrow 4:score  2:brick").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 16));
+
+        let err = Level::parse("row 4:score:hp=0").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 16));
+
+        let err = Level::parse("row 30:solid 20:score").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 14));
+
+        let err = Level::parse("row 40:gap 2147483647:score").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 12));
+
+        let err = Level::parse("row :").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 5));
+
+        let err = Level::parse("row 4:score ::").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 13));
+
+        let err = Level::parse("speed base=300 max=x").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 20));
+
+        let err = Level::parse("row 4:score\n  bonus stash").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 3));
+    }
+}
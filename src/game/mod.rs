@@ -1,18 +1,36 @@
 
+mod arena;
+mod autopilot;
+mod campaign;
+mod effects;
+mod events;
+mod grid;
+mod level;
+mod particles;
 mod pickups;
+mod replay;
 mod scoring;
 
-pub use pickups::PickupKind;
+pub use {
+    arena::{Arena, Id as BlockId},
+    autopilot::{Autopilot, Controller},
+    campaign::{Campaign, CampaignError, LevelSource, LevelSummary, Progress},
+    effects::{Effect, EffectKind, Effects},
+    events::Event,
+    level::{Level, LoadError, ParseError, SpeedCurve},
+    particles::{Particle, ParticleKind, Particles},
+    pickups::{Pickup, PickupKind},
+    replay::{Playback, Replay, Script},
+    scoring::{BonusMode, Rank, Scoring},
+};
 
 use {
-    self::{
-        pickups::*,
-        scoring::*,
-    },
+    self::{grid::Grid, pickups::*},
     crate::{
-        block::{self, Block},
+        block::{self, Block, Patrol},
         collider::{Collider, Collision},
         dilate::Dilate,
+        gjk::gjk,
         math::*,
     },
     rand::{
@@ -23,16 +41,49 @@ use {
     pcg_rand::Pcg32Basic,
 };
 
+pub const FRAMERATE: u32 = 180;
+pub const DT:        f32 = 1. / FRAMERATE as f32;
+
 const PADDLE_Y:         f32 = 40.;
 const PADDLE_W:         f32 = 80.;
+// How much each stack of a paddle size effect scales the paddle by.
+const PADDLE_STACK_SCALE: f32 = 1.3;
 const PADDLE_MAX_SPEED: f32 = 600.;
 const PADDLE_ACC:       f32 = 6000.;
 const PADDLE_FRICTION:  f32 = 7.;
 
+// Off the top of the paddle the ball leaves at an angle from vertical set by where it
+// struck, with some extra "english" from the paddle's motion, all within the cone.
+const PADDLE_MAX_ANGLE: f32 = 1.05;
+const PADDLE_ENGLISH:   f32 = 0.25;
+
 const BALL_SIZE:         f32 = 12.;
 const BALL_SERVE_SPEED:  f32 = 400.;
 const BALL_SERVE_COSINE: f32 = 0.7;
 
+// Collisions handled per ball per tick before giving up on the rest of the motion.
+const MAX_BOUNCES: usize = 8;
+
+const STARTING_LIVES: i32 = 3;
+
+// Unless a level says otherwise.
+const BONUS_MODE: BonusMode = BonusMode::Combo;
+
+const DETONATOR_RADIUS: f32 = 60.;
+
+// How far an explosive block reaches for neighbours to hit.
+const EXPLOSION_REACH: f32 = 4.;
+
+// Defaults for armored blocks' minimum ball speed and regenerating blocks' delay.
+const ARMOR_SPEED: f32 = 480.;
+const REGEN_DELAY: f32 = 4.;
+
+// Default speed of blocks on patrol.
+const PATROL_PACE: f32 = 60.;
+
+const MULTI_BALL_COUNT:  usize = 2;
+const MULTI_BALL_SPREAD: f32   = 0.4;
+
 const GAME_WIDTH:  i32 = 600;
 const GAME_HEIGHT: i32 = 600;
 
@@ -46,23 +97,30 @@ const BLOCKS_VERT: i32 = 10;
 const BLOCK_H: i32 = 30;
 const SPLIT_STEP: i32 = BLOCK_H / 2;
 
+fn walls_rect() -> Rect {
+    Rect::new(
+        P2::new(GAME_LEFT  as f32, 0.),
+        P2::new(GAME_RIGHT as f32, GAME_HEIGHT as f32)
+    )
+}
+
 fn serve_position(paddle_x: f32) -> P2 {
     P2::new(paddle_x, PADDLE_Y + 8.)
 }
 
-fn serve_velocity(paddle_vel: f32) -> V2 {
+fn serve_velocity(paddle_vel: f32, speed: f32) -> V2 {
     let x_dir = if paddle_vel.abs () > 0.01 { paddle_vel.signum() }
                 else                        { 0. };
 
     let dir = V2::new(BALL_SERVE_COSINE * x_dir, 1.).normalize();
-    dir * BALL_SERVE_SPEED
+    dir * speed
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum EntityID {
     Walls,
     Paddle,
-    Block(usize)
+    Block(BlockId)
 }
 
 #[derive(Clone, Debug)]
@@ -83,44 +141,59 @@ struct Hit {
     id:        EntityID,
 }
 
+// A ball caught by the paddle, riding along `offset` from its centre.
+#[derive(Clone, Copy, Debug)]
+struct HeldBall {
+    offset: f32,
+    speed:  f32,
+}
+
+// What became of a ball over a tick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Fate {
+    Flying,
+    Caught,
+    Lost,
+}
+
 struct FlyingBall {
     pos:            P2,
     prev_pos:       P2,
     vel:            V2,
-    prev_collision: Option<(EntityID, V2)>,
-}
-
-enum Ball {
-    Flying(FlyingBall),
-    Serving
+    speed:          f32,
 }
 
-impl Ball {
-    fn position(&self, alpha: f32) -> Option<P2> {
-        if let Ball::Flying(ball) = self {
-            let pos = ball.prev_pos.coords.lerp(&ball.pos.coords, alpha).into();
-            Some(pos)
-        }
-        else {
-            None
-        }
-    }
-
-    fn serve(&mut self, pos: P2, vel: V2) {
-        let ball = FlyingBall {
+impl FlyingBall {
+    fn new(pos: P2, vel: V2) -> FlyingBall {
+        FlyingBall {
             pos,
             prev_pos: pos,
             vel,
-            prev_collision: None,
-        };
-        *self = Ball::Flying(ball);
+            speed: vel.norm(),
+        }
+    }
+
+    fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+        self.vel = self.vel.normalize() * speed;
+    }
+
+    fn speed_up(&mut self, amount: f32, curve: &SpeedCurve) {
+        self.set_speed((self.speed + amount).min(curve.max));
     }
 
-    fn kill(&mut self) {
-        *self = Ball::Serving;
+    fn position(&self, alpha: f32) -> P2 {
+        self.prev_pos.coords.lerp(&self.pos.coords, alpha).into()
     }
 }
 
+fn paddle_rect(width: f32) -> Rect {
+    Rect::new(
+        P2::new(-width * 0.5, -6.),
+        P2::new( width * 0.5,  0.)
+    )
+}
+
 fn get_collision<'a> (solids: impl IntoIterator<Item = &'a SolidEntity>, motion: Segment)
     -> Option<Hit>
 {
@@ -139,15 +212,35 @@ pub struct State {
     paddle_vel:    f32,
     paddle_prev_x: f32,
 
-    ball_rect: Rect,
-    ball:      Ball,
+    ball:  Circle,
+    balls: Vec<FlyingBall>,
+    held:  Vec<HeldBall>,
+    // whether serve was pressed last tick, so held balls go on a fresh press
+    serve_pressed: bool,
+
+    speed:       SpeedCurve,
+    serve_speed: f32,
 
-    blocks: Vec<Block>,
+    blocks: Arena<Block>,
+    grid:   Grid,
+    // bumped whenever a block is damaged or removed
+    blocks_revision: u64,
     pickups: Pickups,
 
+    particles: Particles,
+
+    effects: Effects,
+
+    detonator_armed: bool,
+
     scoring: Scoring,
+    lives:   i32,
 
     solids: Vec<SolidEntity>,
+    // the blocks near the motion being swept, kept to save reallocating each bounce
+    nearby: Vec<BlockId>,
+
+    events: Vec<Event>,
 }
 
 #[derive(Clone)]
@@ -157,16 +250,32 @@ pub struct Frame<'a> {
     pub paddle_rect: Rect,
     pub paddle_pos:  P2,
 
-    pub ball_rect: Rect,
-    pub ball_pos:  P2,
+    pub ball_radius:    f32,
+    pub ball_positions: Vec<P2>,
+    pub ball_speed:     f32,
 
-    pub blocks: &'a Vec<Block>,
+    pub blocks:          &'a Arena<Block>,
+    pub blocks_revision: u64,
+    // where the blocks on patrol are drawn, which `blocks_revision` doesn't follow
+    pub moving_blocks:   Vec<(BlockId, Rect)>,
     pub pickups: &'a Pickups,
 
+    pub particles: &'a Particles,
+
+    pub effects: &'a Effects,
+
     pub scoring: Scoring,
+    pub lives:   i32,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Playing,
+    Cleared,
+    GameOver,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Input {
     pub paddle_dir: i32,
     pub serve:      bool,
@@ -174,75 +283,15 @@ pub struct Input {
 
 impl State {
     pub fn new(seed: u64) -> State {
-        let paddle_rect = Rect::new(
-            P2::new(-PADDLE_W * 0.5, -6.),
-            P2::new( PADDLE_W * 0.5,  0.)
-        );
-
-        let ball_rect = Rect::new(
-            P2::new(-BALL_SIZE * 0.5, -BALL_SIZE * 0.5),
-            P2::new( BALL_SIZE * 0.5,  BALL_SIZE * 0.5)
-        );
-
-        let mut rand = Pcg32Basic::seed_from_u64(seed);
-        let next_rand = Pcg32Basic::seed_from_u64(rand.next_u64());
-        let mut split_distro = Bernoulli::new(0.3)
-            .unwrap()
-            .sample_iter(rand);
-
-        const LAST_SPLIT: i32 = GAME_WIDTH / SPLIT_STEP;
-        let splits: Vec<Vec<_>> = (1..BLOCKS_VERT)
-            .map(|_| {
-                let splits = (1 .. LAST_SPLIT)
-                    .zip(split_distro.by_ref())
-                    .filter(|(_, keep)| *keep)
-                    .map(|(i, _)| i);
-                let right_end = std::iter::once(LAST_SPLIT);
-                splits.chain(right_end).collect()
-            })
-            .collect();
+        State::from_level(seed, Level::generate(seed))
+    }
 
-        let block_keep_distro = Bernoulli::new(0.95)
-            .unwrap()
-            .sample_iter(next_rand);
-
-        let blocks = splits.iter()
-            .enumerate()
-            .flat_map(|(y_index, splits)| {
-                let y0 = (GAME_TOP - (y_index + 2) as i32 * BLOCK_H) as f32;
-                let y1 = y0 + BLOCK_H as f32;
-
-                splits.iter()
-                    .scan(0, move |l, r| {
-                        assert!(*r > *l);
-
-                        let x0 = GAME_LEFT as f32 + (*l * SPLIT_STEP) as f32;
-                        let x1 = GAME_LEFT as f32 + (*r * SPLIT_STEP) as f32;
-                        let rect = Rect::new(
-                            P2::new(x0 + 0., y0 + 0.),
-                            P2::new(x1 - 0., y1 - 0.)
-                        );
-
-                        let w = *r - *l;
-
-                        use block::Kind::*;
-                        let block = if w > 8 {
-                            Block { kind: Invlunerable, rect: rect.contract(8.) }
-                        }
-                        else {
-                            Block { kind: Scoring { score: w * 10, hp: w }, rect }
-                        };
-
-                        assert!(rect.width() > 0.);
-
-                        *l = *r;
-                        Some(block)
-                    })
-            })
-            .zip(block_keep_distro)
-            .filter(|(_, keep)| *keep)
-            .map(|(block, _)| block)
-            .collect();
+    pub fn from_level(seed: u64, level: Level) -> State {
+        let paddle_rect = paddle_rect(PADDLE_W);
+
+        let ball = Circle::new_centered(BALL_SIZE * 0.5);
+
+        let blocks: Arena<Block> = level.blocks.into_iter().collect();
 
         State {
             paddle_rect,
@@ -250,18 +299,50 @@ impl State {
             paddle_prev_x: 0.,
             paddle_vel:    0.,
 
-            ball_rect,
-            ball: Ball::Serving,
+            ball,
+            balls: Vec::new(),
+            held:  Vec::new(),
+            serve_pressed: false,
 
+            speed:       level.speed,
+            serve_speed: level.speed.base,
+
+            grid:   Grid::new(&blocks),
             blocks,
+            blocks_revision: 0,
             pickups: Pickups::new(seed),
 
-            scoring: Scoring::new(),
+            particles: Particles::new(seed),
+
+            effects: Effects::new(),
+
+            detonator_armed: false,
+
+            scoring: Scoring::new(level.bonus_mode),
+            lives:   STARTING_LIVES,
 
             solids: Vec::new(),
+            nearby: Vec::new(),
+
+            events: Vec::new(),
         }
     }
 
+    // The events of the tick that cleared `previous` come along too, as do any
+    // particles still in flight.
+    fn carry_over(&mut self, previous: &mut State) {
+        self.scoring = previous.scoring.next_level(self.scoring.bonus_mode);
+        self.lives   = previous.lives;
+        self.events  = std::mem::take(&mut previous.events);
+        self.blocks_revision = previous.blocks_revision + 1;
+        std::mem::swap(&mut self.particles, &mut previous.particles);
+    }
+
+    // What happened during the latest update.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
     pub fn rect(&self) -> Rect {
         let mins = P2::new(GAME_LEFT  as f32, GAME_BOTTOM as f32);
         let dims = V2::new(GAME_WIDTH as f32, GAME_HEIGHT as f32);
@@ -274,68 +355,310 @@ impl State {
         let paddle_x = lerp(self.paddle_prev_x, self.paddle_x, alpha);
         let paddle_pos = P2::new(paddle_x, PADDLE_Y);
 
-        let ball_pos = self.ball.position(alpha)
-            .unwrap_or(paddle_pos + V2::new(0., 10.));
+        let mut ball_positions: Vec<P2> = self.balls.iter()
+            .map(|ball| ball.position(alpha))
+            .chain(self.held.iter().map(|held| self.held_position(held, paddle_x)))
+            .collect();
+
+        if ball_positions.is_empty() {
+            ball_positions.push(paddle_pos + V2::new(0., 10.));
+        }
+
+        let moving_blocks = self.blocks.iter()
+            .filter(|(_, block)| block.is_moving())
+            .map(|(id, block)| (id, block.rect_at(alpha)))
+            .collect();
+
+        let ball_speed = self.balls.iter()
+            .map(|ball| ball.speed)
+            .chain(self.held.iter().map(|held| held.speed))
+            .max_by_key(|speed| OrdF32(*speed))
+            .unwrap_or(self.serve_speed);
 
         Frame {
-            rect: self.rect(),
+            rect,
 
             paddle_rect: self.paddle_rect,
             paddle_pos,
 
-            ball_rect: self.ball_rect,
-            ball_pos,
+            ball_radius: self.ball.radius,
+            ball_positions,
+            ball_speed,
 
-            blocks: &self.blocks,
+            blocks:          &self.blocks,
+            blocks_revision: self.blocks_revision,
+            moving_blocks,
             pickups: &self.pickups,
 
+            particles: &self.particles,
+
+            effects: &self.effects,
+
             scoring: self.scoring,
+            lives:   self.lives,
         }
     }
 
-    fn get_solids_for_entity(&mut self, entity: Rect) {
+    // Gathers the solids `entity` might run into along `motion`, leaving the blocks
+    // near it in `nearby`.
+    fn get_solids_for_entity(&mut self, entity: Circle, motion: Segment) {
         self.solids.clear();
+        self.grid.query_motion(motion, entity.radius, &mut self.nearby);
 
         use rect::CollideFrom;
-        for (block_index, block) in self.blocks.iter().enumerate() {
+        for &block_id in &self.nearby {
+            // moving blocks are swept separately
+            let block = &self.blocks[block_id];
+            if block.is_moving() || !block.is_solid() {
+                continue;
+            }
+
             self.solids.push(SolidEntity::new(
-                block.rect
+                self.blocks[block_id].rect
                     .expand(entity)
                     .to_collider(CollideFrom::Outside),
-                EntityID::Block(block_index)
+                EntityID::Block(block_id)
             ));
         }
 
-        let wall_collider = Rect::new(
-                P2::new(GAME_LEFT  as f32, 0.),
-                P2::new(GAME_RIGHT as f32, GAME_HEIGHT as f32)
-            )
+        let wall_collider = walls_rect()
             .expand(entity)
             .to_collider(CollideFrom::Inside);
         self.solids.push(SolidEntity::new(wall_collider, EntityID::Walls));
+    }
 
-        let paddle_collider = self.paddle_rect
-            .at(P2::new(self.paddle_x, PADDLE_Y))
-            .expand(entity)
-            .side_max_y()
-            .to_collider();
-        self.solids.push(SolidEntity::new(paddle_collider, EntityID::Paddle));
+    fn paddle_x_at(&self, time: f32, dt: f32) -> f32 {
+        lerp(self.paddle_prev_x, self.paddle_x, time / dt)
+    }
+
+    // Sweeps the ball against the paddle over the part of the tick starting at `time`,
+    // with both of them moving.
+    fn paddle_collision(&self, motion: Segment, time: f32, dt: f32) -> Option<Hit> {
+        let from = P2::new(self.paddle_x_at(time, dt), PADDLE_Y);
+        let to   = P2::new(self.paddle_x, PADDLE_Y);
+        let paddle_path = Segment::new_from_points(from, to);
+
+        let swept = self.paddle_rect.at(from).union(&self.paddle_rect.at(to));
+        let reach = Rect::new(motion.source(), motion.destination()).expand(self.ball.radius);
+        if !swept.overlaps(&reach) {
+            return None;
+        }
+
+        let mut collision = gjk(motion, self.ball, paddle_path, self.paddle_rect)?;
+
+        // report the ball's centre, as the other colliders do
+        collision.point = motion.source() + motion.stride() * collision.param;
+        Some(Hit { collision, id: EntityID::Paddle })
+    }
+
+    fn paddle_bounce(&mut self, ball: &mut FlyingBall, normal: V2, paddle_x: f32) {
+        self.events.push(Event::PaddleHit { position: ball.pos });
+
+        // bounce in the paddle's frame, so a paddle moving into the ball bats it away
+        let paddle_vel = V2::new(self.paddle_vel, 0.);
+        let relative = ball.vel - paddle_vel;
+        if relative.dot(&normal) < 0. {
+            ball.vel = reflect(relative, normal) + paddle_vel;
+        }
+
+        // a hit on the side keeps whatever speed it was batted away with, within limits
+        if normal.y <= 0. {
+            let speed = ball.vel.norm().clamp(ball.speed, self.speed.max);
+            ball.set_speed(speed);
+        }
+        else {
+            let value = self.scoring.hit_paddle();
+            if value != 0 {
+                self.events.push(Event::ComboEnded { value, forfeit: false });
+            }
+
+            ball.vel = self.paddle_direction(ball.pos.x - paddle_x) * ball.speed;
+            ball.speed_up(self.speed.hit, &self.speed);
+        }
     }
 
-    fn update_ball(&mut self, dt: f32) {
-        self.get_solids_for_entity(self.ball_rect);
+    // The way a ball leaves the top of the paddle, steered by where on it the ball
+    // is, and by the paddle's motion.
+    fn paddle_direction(&self, offset: f32) -> V2 {
+        let reach = self.paddle_rect.maxs.x + self.ball.radius;
+        let offset = (offset / reach).clamp(-1., 1.);
+        let english = self.paddle_vel / PADDLE_MAX_SPEED * PADDLE_ENGLISH;
+        let angle = (offset * PADDLE_MAX_ANGLE + english)
+            .clamp(-PADDLE_MAX_ANGLE, PADDLE_MAX_ANGLE);
+
+        V2::new(angle.sin(), angle.cos())
+    }
+
+    // Held balls stay on the paddle, even if it shrinks under them, and as far out as
+    // the walls let a flying ball go when it pokes out past them.
+    fn held_position(&self, held: &HeldBall, paddle_x: f32) -> P2 {
+        let half = self.paddle_rect.maxs.x;
+        let bounds = walls_rect().expand(self.ball.radius);
+        let x = (paddle_x + held.offset.clamp(-half, half)).clamp(bounds.mins.x, bounds.maxs.x);
+        serve_position(x)
+    }
+
+    fn launch_held(&mut self) {
+        for held in std::mem::take(&mut self.held) {
+            let pos = self.held_position(&held, self.paddle_x);
+            let offset = pos.x - self.paddle_x;
+            let ball = FlyingBall::new(pos, self.paddle_direction(offset) * held.speed);
+            self.events.push(Event::BallServed { position: ball.pos });
+            self.balls.push(ball);
+        }
+    }
+
+    fn spawn_multi_ball(&mut self) {
+        let (pos, vel) = match self.balls.first() {
+            Some(ball) => (ball.pos, ball.vel),
+            None       => (serve_position(self.paddle_x), serve_velocity(self.paddle_vel, self.serve_speed)),
+        };
+
+        for i in 1 ..= MULTI_BALL_COUNT {
+            let side = if i % 2 == 0 { 1. } else { -1. };
+            let angle = side * MULTI_BALL_SPREAD * i.div_ceil(2) as f32;
+            self.balls.push(FlyingBall::new(pos, rotate(vel, angle)));
+        }
+    }
 
-        let ball = match &mut self.ball {
-            Ball::Flying(ball) => ball,
-            Ball::Serving      => { return; }
+    // Returns the number of blocks broken, counting any set off by explosions. Blocks
+    // that are already gone are left alone.
+    fn hit_block(&mut self, id: BlockId, point: P2, speed: f32) -> usize {
+        let (kind, hit) = match self.blocks.get_mut(id) {
+            Some(block) => (block.kind, block.hit(speed)),
+            None        => { return 0; }
         };
 
+        use block::Hit::*;
+        match hit {
+            Broken(score) | Exploded(score) => {
+                let block = self.blocks.remove(id).unwrap();
+                let rect = block.rect;
+                self.grid.remove(id, block.bounds());
+                self.blocks_revision += 1;
+
+                let score = self.scoring.block_broken(score as i64);
+                self.events.push(Event::BlockBroken { id, score, rect, kind });
+
+                if let Some(pickup) = self.pickups.block_broken(block) {
+                    let Pickup { kind, position, .. } = pickup;
+                    self.events.push(Event::PickupSpawned { kind, position });
+                }
+
+                if let Exploded(_) = hit {
+                    self.events.push(Event::BlockExploded { id, rect });
+                    1 + self.explode(rect)
+                }
+                else {
+                    1
+                }
+            }
+
+            Damaged => {
+                self.blocks_revision += 1;
+                self.scoring.block_damaged();
+                let hp = self.blocks[id].hp().unwrap_or(0);
+                self.events.push(Event::BlockDamaged { id, hp, position: point, kind });
+                0
+            }
+
+            Switched(link) => {
+                self.toggle_gates(link);
+                0
+            }
+
+            Deflected | Invlunerable => 0
+        }
+    }
+
+    fn toggle_gates(&mut self, link: u32) {
+        for (id, block) in self.blocks.iter_mut() {
+            if let block::Kind::Gate { link: gate_link, open } = &mut block.kind {
+                if *gate_link == link {
+                    *open = !*open;
+                    self.events.push(Event::GateToggled { id, open: *open });
+                }
+            }
+        }
+
+        self.blocks_revision += 1;
+    }
+
+    // Hits every block within reach of `rect` as hard as can be. Returns the number of
+    // blocks broken.
+    fn explode(&mut self, rect: Rect) -> usize {
+        let area = rect.expand(EXPLOSION_REACH);
+        let neighbours: Vec<BlockId> = self.grid.query(area).into_iter()
+            .filter(|id| self.blocks[*id].rect.overlaps(&area))
+            .collect();
+
+        let mut broken = 0;
+        for id in neighbours {
+            let center = self.blocks.get(id).map(|block| block.rect.mins + block.rect.dims() * 0.5);
+            if let Some(center) = center {
+                broken += self.hit_block(id, center, f32::INFINITY);
+            }
+        }
+
+        broken
+    }
+
+    // Returns the number of blocks broken.
+    fn detonate(&mut self, point: P2) -> usize {
+        let in_range: Vec<BlockId> = self.blocks.iter()
+            .filter(|(_, block)| block.rect.distance_to(point) <= DETONATOR_RADIUS)
+            .map(|(id, _)| id)
+            .collect();
+
+        let mut broken = 0;
+        for id in in_range {
+            broken += self.hit_block(id, point, f32::INFINITY);
+        }
+
+        broken
+    }
+
+    // Looks among the blocks `get_solids_for_entity` found near `motion`.
+    fn moving_block_collision(&self, motion: Segment, time: f32, dt: f32) -> Option<Hit> {
+        self.nearby.iter()
+            .copied()
+            .filter(|id| self.blocks[*id].is_moving() && self.blocks[*id].is_solid())
+            .filter_map(|id| {
+                let block = &self.blocks[id];
+                let from = block.rect_at(time / dt).mins;
+                let block_path = Segment::new_from_points(from, block.rect.mins);
+                let shape = Rect::new_with_dims(P2::origin(), block.rect.dims());
+
+                let mut collision = gjk(motion, self.ball, block_path, shape)?;
+                collision.point = motion.source() + motion.stride() * collision.param;
+                Some(Hit { collision, id: EntityID::Block(id) })
+            })
+            .min_by_key(|hit| OrdF32(hit.collision.param))
+    }
+
+    fn update_ball(&mut self, ball: &mut FlyingBall, dt: f32) -> Fate {
+        ball.prev_pos = ball.pos;
+        ball.speed_up(self.speed.time * dt, &self.speed);
+
         let mut remaining = dt;
-        while remaining > 0. {
-            ball.prev_pos = ball.pos;
+        for _ in 0 .. MAX_BOUNCES {
+            if remaining <= 0. {
+                break;
+            }
+
             let motion = Segment::new(ball.pos, ball.vel * remaining);
+            self.get_solids_for_entity(self.ball, motion);
+
+            let solid_hit  = get_collision(&self.solids, motion);
+            let paddle_hit = self.paddle_collision(motion, dt - remaining, dt);
+            let moving_hit = self.moving_block_collision(motion, dt - remaining, dt);
+
+            let hit = solid_hit.into_iter()
+                .chain(paddle_hit)
+                .chain(moving_hit)
+                .min_by_key(|hit| OrdF32(hit.collision.param));
 
-            let Hit { collision, id } = match get_collision(&self.solids, motion) {
+            let Hit { collision, id } = match hit {
                 Some(hit) => hit,
                 None => {
                     ball.pos = motion.destination();
@@ -343,109 +666,563 @@ impl State {
                 }
             };
 
-            ball.vel = reflect(ball.vel, collision.normal);
             ball.pos = collision.point;
 
+            let time = dt - remaining * (1. - collision.param);
+
             use EntityID::*;
+            if id == Paddle {
+                self.paddle_bounce(ball, collision.normal, self.paddle_x_at(time, dt));
+                if collision.normal.y > 0. && self.effects.is_active(EffectKind::Catch) {
+                    self.events.push(Event::BallCaught { position: ball.pos });
+                    return Fate::Caught;
+                }
+            }
+            else {
+                let carry = match id {
+                    Block(id) => self.blocks[id].velocity(dt),
+                    _         => V2::zeros(),
+                };
+
+                if carry == V2::zeros() {
+                    ball.vel = reflect(ball.vel, collision.normal);
+                }
+                else {
+                    // bounce as seen from the moving block; one coming on faster than
+                    // the ball knocks it along quicker
+                    ball.vel = reflect(ball.vel - carry, collision.normal) + carry;
+                    let max = self.speed.max.max(ball.speed);
+                    ball.set_speed(ball.vel.norm().clamp(ball.speed, max));
+                }
+            }
+
             match id {
                 Walls if collision.normal.y > 0. => {
-                    self.scoring.hit_floor();
-                    self.ball.kill();
-                    return;
+                    return Fate::Lost;
                 }
 
-                Paddle => {
-                    self.scoring.hit_paddle();
-
-                    if self.paddle_vel.abs() > 5. {
-                        ball.vel.x = ball.vel.x.abs()
-                                   * self.paddle_vel.signum();
-                    }
+                Walls => {
+                    let position = ball.pos - collision.normal * self.ball.radius;
+                    self.events.push(Event::WallHit { position, normal: collision.normal });
                 }
 
-                Block(index) => {
-                    use block::Hit::*;
-                    match self.blocks[index].hit() {
-                        Broken(score) => {
-                            let block = self.blocks.remove(index);
-                            self.scoring.block_broken(score as i64);
-                            self.pickups.block_broken(block);
-                        }
-
-                        Damaged => {
-                            self.scoring.block_damaged();
-                        }
+                Block(_) if self.detonator_armed => {
+                    self.detonator_armed = false;
+                    let contact = ball.pos - collision.normal * self.ball.radius;
+                    let broken = self.detonate(contact);
+                    ball.speed_up(self.speed.block * broken as f32, &self.speed);
+                }
 
-                        Invlunerable => { }
+                Block(id) => {
+                    let broken = self.hit_block(id, collision.point, ball.speed);
+                    if broken > 0 {
+                        ball.speed_up(self.speed.block * broken as f32, &self.speed);
                     }
                 }
 
                 _ => { }
             };
 
-            remaining -= collision.param;
+            remaining -= collision.param * remaining;
+        }
+
+        // a paddle poking out past a wall can shove the ball through it, and once it's
+        // on the wall's line the wall no longer catches it; put it back, heading in
+        let bounds = walls_rect().expand(self.ball.radius);
+        if ball.pos.x < bounds.mins.x {
+            ball.pos.x = bounds.mins.x;
+            ball.vel.x = ball.vel.x.abs();
         }
+        else if ball.pos.x > bounds.maxs.x {
+            ball.pos.x = bounds.maxs.x;
+            ball.vel.x = -ball.vel.x.abs();
+        }
+
+        if ball.pos.y > bounds.maxs.y {
+            ball.pos.y = bounds.maxs.y;
+            ball.vel.y = -ball.vel.y.abs();
+        }
+
+        Fate::Flying
     }
 
-    pub fn update(&mut self, dt: f32, input: Input) -> bool {
+    // Sizes the paddle to suit the effects on it.
+    fn resize_paddle(&mut self) {
+        let wide   = self.effects.stacks(EffectKind::WidePaddle)   as i32;
+        let narrow = self.effects.stacks(EffectKind::NarrowPaddle) as i32;
+        self.paddle_rect = paddle_rect(PADDLE_W * PADDLE_STACK_SCALE.powi(wide - narrow));
+    }
+
+    pub fn update(&mut self, dt: f32, input: Input) -> Status {
+        self.events.clear();
         self.paddle_prev_x = self.paddle_x;
 
+        for kind in self.effects.update(dt) {
+            self.events.push(Event::EffectEnded { kind });
+        }
+        self.resize_paddle();
+
         //let friction = self.paddle_vel.signum() * (self.paddle_vel * self.paddle_vel) * 0.02;
         let friction = self.paddle_vel * PADDLE_FRICTION;
         let paddle_acc = input.paddle_dir as f32 * PADDLE_ACC - friction;
 
         self.paddle_vel = (self.paddle_vel + dt * paddle_acc)
-            .min( PADDLE_MAX_SPEED)
-            .max(-PADDLE_MAX_SPEED);
+            .clamp(-PADDLE_MAX_SPEED, PADDLE_MAX_SPEED);
 
+        // the paddle can poke halfway out past the walls
         let old_paddle_x = self.paddle_x;
-        const PADDLE_X_POKEOUT: f32 = PADDLE_W * 0.5;
-        const PADDLE_X_BOUND: f32 = (GAME_WIDTH as f32 - PADDLE_W + PADDLE_X_POKEOUT) * 0.5;
+        let paddle_w = self.paddle_rect.width();
+        let paddle_x_bound = (GAME_WIDTH as f32 - paddle_w * 0.5) * 0.5;
         self.paddle_x = (self.paddle_x + dt * self.paddle_vel)
-            .min( PADDLE_X_BOUND)
-            .max(-PADDLE_X_BOUND);
+            .clamp(-paddle_x_bound, paddle_x_bound);
         self.paddle_vel = (self.paddle_x - old_paddle_x) / dt;
 
         if self.paddle_vel.abs() < 0.1 {
             self.paddle_vel = 0.;
         }
 
+        for (id, block) in self.blocks.iter_mut() {
+            if block.update(dt) {
+                let hp = block.hp().unwrap_or(0);
+                self.events.push(Event::BlockHealed { id, hp });
+                self.blocks_revision += 1;
+            }
+        }
+
         let paddle_rect = self.paddle_rect.at(P2::new(self.paddle_x, PADDLE_Y));
         let collected = self.pickups.update(dt, paddle_rect, 0.);
 
         for pickup in collected {
-            println!("got {:?}!", pickup);
-        }
-
-        match self.ball {
-            Ball::Flying(_) => {
-                self.update_ball(dt);
+            self.events.push(Event::PickupCollected { kind: pickup });
+            match pickup {
+                PickupKind::Bonus(amount) => self.scoring.bonus_collected(amount as i64),
+                PickupKind::MultiBall     => self.spawn_multi_ball(),
+                PickupKind::ExtraBall     => self.lives += 1,
+                PickupKind::Detonator     => self.detonator_armed = true,
+                PickupKind::WidePaddle    => self.effects.start(EffectKind::WidePaddle),
+                PickupKind::NarrowPaddle  => self.effects.start(EffectKind::NarrowPaddle),
+                PickupKind::Catch         => self.effects.start(EffectKind::Catch),
             }
+        }
+        self.resize_paddle();
 
-            Ball::Serving if input.serve => {
-                self.ball.serve(
+        if self.balls.is_empty() && self.held.is_empty() {
+            if input.serve {
+                let ball = FlyingBall::new(
                     serve_position(self.paddle_x),
-                    serve_velocity(self.paddle_vel),
+                    serve_velocity(self.paddle_vel, self.serve_speed),
                 );
+                self.events.push(Event::BallServed { position: ball.pos });
+                self.balls.push(ball);
             }
+        }
+        else {
+            let mut balls = std::mem::take(&mut self.balls);
+
+            let mut index = 0;
+            while index < balls.len() {
+                match self.update_ball(&mut balls[index], dt) {
+                    Fate::Flying => {
+                        index += 1;
+                        continue;
+                    }
+
+                    Fate::Caught => {
+                        let caught = balls.remove(index);
+                        let offset = caught.pos.x - self.paddle_x;
+                        self.held.push(HeldBall { offset, speed: caught.speed });
+                        continue;
+                    }
+
+                    Fate::Lost => { }
+                }
 
-            _ => { }
+                let lost = balls.remove(index);
+                let life_lost = balls.is_empty() && self.held.is_empty();
+                self.events.push(Event::FloorHit { position: lost.pos, life_lost });
+
+                if life_lost {
+                    let value = self.scoring.hit_floor();
+                    if value != 0 {
+                        self.events.push(Event::ComboEnded { value, forfeit: true });
+                    }
+                    self.lives -= 1;
+
+                    let gained = lost.speed - self.speed.base;
+                    self.serve_speed = self.speed.base + gained * self.speed.keep;
+                }
+            }
+
+            self.balls = balls;
+
+            if input.serve && !self.serve_pressed {
+                self.launch_held();
+            }
         }
 
-        let cleared = self.scoring.no_combo() && self.blocks.iter()
+        self.serve_pressed = input.serve;
+
+        self.particles.react(&self.events);
+        self.particles.update(dt);
+
+        if self.lives <= 0 {
+            return Status::GameOver;
+        }
+
+        // neither switches nor gates ever need breaking
+        let cleared = self.scoring.no_combo() && self.blocks.values()
             .filter(|block| block.is_scoring())
             .count()
             == 0;
 
         if cleared {
-            println!("A winner is you!");
-            println!("Score:     {:8}",  self.scoring.score);
-            println!("Penalties: {:8}",  self.scoring.penalties);
-            println!("Rank:      {:>8}", self.scoring.rank());
-            false
+            self.events.push(Event::LevelCleared);
+            Status::Cleared
         }
         else {
-            true
+            Status::Playing
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_level() -> Level {
+        Level { name: None, speed: SpeedCurve::default(), bonus_mode: BONUS_MODE, blocks: Vec::new() }
+    }
+
+    #[test]
+    fn test_paddle_bats_ball_from_the_side() {
+        let mut state = State::from_level(0, empty_level());
+        state.paddle_vel = PADDLE_MAX_SPEED;
+
+        let pos = P2::new((PADDLE_W + BALL_SIZE) * 0.5 + 1., PADDLE_Y - 3.);
+        state.balls.push(FlyingBall::new(pos, V2::new(-50., -50.)));
+
+        state.update(DT, Input { paddle_dir: 1, serve: false });
+
+        let ball = &state.balls[0];
+        assert!(ball.vel.x > 0.);
+        assert!(ball.pos.x - state.paddle_x >= (PADDLE_W + BALL_SIZE) * 0.5 - 0.01);
+    }
+
+    #[test]
+    fn test_only_the_last_ball_down_costs_a_life() {
+        let mut state = State::from_level(0, empty_level());
+        state.balls.push(FlyingBall::new(P2::new(-200., 300.), V2::new(0., 400.)));
+        state.spawn_multi_ball();
+
+        // the new balls fan out from the first at the same speed
+        assert_eq!(state.balls.len(), 1 + MULTI_BALL_COUNT);
+        for ball in &state.balls[1 ..] {
+            assert_eq!(ball.pos, state.balls[0].pos);
+            assert!((ball.speed - 400.).abs() < 0.01);
+            assert!(ball.vel.x != 0.);
+        }
+
+        state.balls.truncate(2);
+        state.balls[0].vel = V2::new(0., -400.);
+        state.balls[1].vel = V2::new(0., 100.);
+
+        let floor_hits = |state: &State| -> Vec<bool> {
+            state.events().iter()
+                .filter_map(|event| match event {
+                    Event::FloorHit { life_lost, .. } => Some(*life_lost),
+                    _                                 => None,
+                })
+                .collect()
+        };
+
+        let idle = Input { paddle_dir: 0, serve: false };
+        while state.balls.len() == 2 {
+            state.update(DT, idle);
+        }
+        assert_eq!(floor_hits(&state), [false]);
+        assert_eq!(state.lives, STARTING_LIVES);
+
+        state.balls[0].vel = V2::new(0., -400.);
+        while !state.balls.is_empty() {
+            state.update(DT, idle);
+        }
+        assert_eq!(floor_hits(&state), [true]);
+        assert_eq!(state.lives, STARTING_LIVES - 1);
+    }
+
+    #[test]
+    fn test_paddle_cannot_push_the_ball_through_a_wall() {
+        let bounds = walls_rect().expand(BALL_SIZE * 0.5);
+        let cases = [
+            (258.,  V2::new(  0.,  -50.)),
+            (262.8, V2::new(300.,  -20.)),
+            (250.,  V2::new( 50., -300.)),
+        ];
+
+        for &(paddle_x, ball_vel) in &cases {
+            let mut state = State::from_level(0, empty_level());
+            state.paddle_x   = paddle_x;
+            state.paddle_vel = PADDLE_MAX_SPEED;
+
+            // wedged between the end of the paddle and the wall
+            state.balls.push(FlyingBall::new(P2::new(bounds.maxs.x - 1., PADDLE_Y - 3.), ball_vel));
+
+            let mut ticks = 0;
+            while !state.balls.is_empty() {
+                state.update(DT, Input { paddle_dir: 1, serve: false });
+                for ball in &state.balls {
+                    assert!(ball.pos.x <= bounds.maxs.x + 0.001, "escaped to {:?} at tick {}", ball.pos, ticks);
+                }
+                ticks += 1;
+                assert!(ticks < FRAMERATE * 10);
+            }
+        }
+    }
+
+    #[test]
+    fn test_paddle_steers_by_hit_position() {
+        let bounce = |offset: f32, paddle_vel: f32| {
+            let mut state = State::from_level(0, empty_level());
+            state.paddle_vel = paddle_vel;
+
+            let mut ball = FlyingBall::new(P2::new(offset, PADDLE_Y + 6.), V2::new(0., -400.));
+            state.paddle_bounce(&mut ball, V2::new(0., 1.), 0.);
+            assert!((ball.vel.norm() - 400. - state.speed.hit).abs() < 0.01);
+            ball.vel.x.atan2(ball.vel.y)
+        };
+
+        assert_eq!(bounce(0., 0.), 0.);
+        assert!(bounce(-20., 0.) < 0.);
+        assert!(bounce( 20., 0.) > bounce(10., 0.));
+        assert!(bounce(10., PADDLE_MAX_SPEED) > bounce(10., 0.));
+        assert!((bounce(100., PADDLE_MAX_SPEED) - PADDLE_MAX_ANGLE).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_ball_speeds_up_and_keeps_some_after_the_floor() {
+        let mut state = State::from_level(0, empty_level());
+        let curve = state.speed;
+
+        state.balls.push(FlyingBall::new(P2::new(0., 300.), V2::new(0., curve.base)));
+        for _ in 0 .. FRAMERATE {
+            state.update(DT, Input { paddle_dir: 0, serve: false });
+        }
+
+        let speed = state.frame(0.).ball_speed;
+        assert!((speed - curve.base - curve.time).abs() < 0.01);
+
+        state.balls[0].set_speed(curve.max * 2.);
+        state.balls[0].speed_up(0., &curve);
+        assert_eq!(state.balls[0].speed, curve.max);
+
+        state.balls[0].pos.x = -200.;
+        state.balls[0].vel = V2::new(0., -curve.max);
+        while !state.balls.is_empty() {
+            state.update(DT, Input { paddle_dir: 1, serve: false });
+        }
+
+        let expected = curve.base + (curve.max - curve.base) * curve.keep;
+        assert!((state.frame(0.).ball_speed - expected).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_explosions_spread_to_neighbours() {
+        let level = Level::parse("
+            row 4:score:hp=1 4:explosive:hp=1 4:explosive:hp=1 4:score:hp=2 4:gap 4:score:hp=1
+            row 4:armored:hp=1:speed=1000
+        ").unwrap();
+        let mut state = State::from_level(0, level);
+        let ids: Vec<BlockId> = state.blocks.iter().map(|(id, _)| id).collect();
+
+        // the chain stops at the block that only takes damage and doesn't cross the gap,
+        // and armour is no help against it
+        let broken = state.hit_block(ids[1], P2::origin(), 0.);
+        assert_eq!(broken, 4);
+        assert_eq!(state.blocks.len(), 2);
+        assert_eq!(state.blocks[ids[3]].hp(), Some(1));
+        assert!(state.blocks.contains(ids[4]));
+        assert!(!state.blocks.contains(ids[5]));
+    }
+
+    #[test]
+    fn test_detonator_blasts_blocks_in_range_once() {
+        let level = Level::parse("row 4:score:hp=2 4:score:hp=1 4:score:hp=2 6:gap 4:score:hp=2").unwrap();
+        let mut state = State::from_level(0, level);
+        let ids: Vec<BlockId> = state.blocks.iter().map(|(id, _)| id).collect();
+        let target = state.blocks[ids[1]].rect;
+
+        state.detonator_armed = true;
+        state.balls.push(FlyingBall::new(P2::new(target.mins.x + 30., 300.), V2::new(0., 400.)));
+        while state.blocks.contains(ids[1]) {
+            state.update(DT, Input { paddle_dir: 0, serve: false });
+        }
+
+        // one blast for every block within reach, and none for the one past the gap,
+        // and the ball bounces off as usual
+        assert!(!state.detonator_armed);
+        assert!(state.balls[0].vel.y < 0.);
+        assert_eq!(state.blocks[ids[0]].hp(), Some(1));
+        assert_eq!(state.blocks[ids[2]].hp(), Some(1));
+        assert_eq!(state.blocks[ids[3]].hp(), Some(2));
+        assert!(state.blocks[ids[3]].rect.distance_to(P2::new(target.mins.x + 30., target.mins.y)) > DETONATOR_RADIUS);
+
+        // the next hit is an ordinary one
+        state.balls[0] = FlyingBall::new(P2::new(target.mins.x - 30., 300.), V2::new(0., 400.));
+        while state.blocks.contains(ids[0]) {
+            state.update(DT, Input { paddle_dir: 0, serve: false });
+        }
+        assert_eq!(state.blocks[ids[2]].hp(), Some(1));
+    }
+
+    #[test]
+    fn test_ball_bounces_off_a_moving_block() {
+        let level = Level::parse("row 1:gap\nrow 1:gap\nrow 8:gap 4:solid:path=-8,0:pace=200").unwrap();
+        let mut state = State::from_level(0, level);
+        let (id, _) = state.blocks.iter().next().unwrap();
+
+        // the block comes across into the ball's path, and knocks it away faster than
+        // it's going itself
+        let start = state.blocks[id].rect.mins + V2::new(-60., BLOCK_H as f32 * 0.5);
+        state.balls.push(FlyingBall::new(start, V2::new(0., 50.)));
+
+        let mut knocked = false;
+        for _ in 0 .. FRAMERATE / 2 {
+            state.update(DT, Input { paddle_dir: 0, serve: false });
+            let block = state.blocks[id].rect;
+            let ball = &state.balls[0];
+            assert!(block.distance_to(ball.pos) >= state.ball.radius - 0.01);
+            knocked |= ball.vel.x < -200.;
+        }
+
+        assert!(knocked);
+    }
+
+    #[test]
+    fn test_switches_work_the_gates() {
+        let level = Level::parse("
+            row 2:switch:link=1 4:gate:link=1 4:gate:link=1:open=1 4:gate:link=2 2:score:hp=1
+        ").unwrap();
+        let mut state = State::from_level(0, level);
+        let ids: Vec<BlockId> = state.blocks.iter().map(|(id, _)| id).collect();
+        let solid = |state: &State| -> Vec<bool> {
+            ids[1 .. 4].iter().map(|id| state.blocks[*id].is_solid()).collect()
+        };
+
+        assert_eq!(solid(&state), [true, false, true]);
+        state.hit_block(ids[0], P2::origin(), 0.);
+        assert_eq!(solid(&state), [false, true, true]);
+        assert!(state.events().contains(&Event::GateToggled { id: ids[1], open: true }));
+
+        // only the scoring block stands between the level and being cleared
+        state.hit_block(ids[4], P2::origin(), 0.);
+        state.scoring.hit_paddle();
+        assert_eq!(state.update(DT, Input { paddle_dir: 0, serve: false }), Status::Cleared);
+    }
+
+    #[test]
+    fn test_paddle_size_effects() {
+        let mut state = State::from_level(0, empty_level());
+        state.effects.start(EffectKind::WidePaddle);
+
+        let right = Input { paddle_dir: 1, serve: false };
+        for _ in 0 .. FRAMERATE {
+            state.update(DT, right);
+        }
+
+        let width = PADDLE_W * PADDLE_STACK_SCALE;
+        assert!((state.paddle_rect.width() - width).abs() < 0.001);
+        assert!((state.paddle_x + width * 0.25 - GAME_RIGHT as f32).abs() < 0.001);
+
+        // back to normal once it wears off, and free to go a little further
+        for _ in 0 .. (EffectKind::WidePaddle.duration() * FRAMERATE as f32) as u32 {
+            state.update(DT, right);
+        }
+
+        assert_eq!(state.effects.stacks(EffectKind::WidePaddle), 0);
+        assert_eq!(state.paddle_rect.width(), PADDLE_W);
+        assert!((state.paddle_x + PADDLE_W * 0.25 - GAME_RIGHT as f32).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_catch_holds_the_ball_until_served_again() {
+        let mut state = State::from_level(0, empty_level());
+        state.effects.start(EffectKind::Catch);
+        state.balls.push(FlyingBall::new(P2::new(20., PADDLE_Y + 50.), V2::new(0., -400.)));
+
+        // serve held down the whole time the ball comes down doesn't count
+        let serve = Input { paddle_dir: 0, serve: true };
+        while state.held.is_empty() {
+            state.update(DT, serve);
+        }
+        assert!(state.events().iter().any(|event| matches!(event, Event::BallCaught { .. })));
+
+        for _ in 0 .. FRAMERATE / 4 {
+            state.update(DT, Input { paddle_dir: -1, serve: true });
+        }
+        assert!(state.balls.is_empty());
+        let ball_x = state.frame(1.).ball_positions[0].x;
+        assert!((ball_x - state.paddle_x - 20.).abs() < 0.01);
+
+        state.update(DT, Input { paddle_dir: 0, serve: false });
+        state.update(DT, serve);
+        assert!(state.held.is_empty());
+        assert!(state.balls[0].vel.x > 0. && state.balls[0].vel.y > 0.);
+    }
+
+    #[test]
+    fn test_held_ball_goes_as_far_out_as_a_flying_one() {
+        let mut state = State::from_level(0, empty_level());
+        for _ in 0 .. FRAMERATE {
+            state.update(DT, Input { paddle_dir: 1, serve: false });
+        }
+
+        // held right at the paddle's end, with the paddle pushed against the wall
+        state.held.push(HeldBall { offset: PADDLE_W * 0.5, speed: 400. });
+        let bounds = walls_rect().expand(state.ball.radius);
+        let held_x = state.frame(1.).ball_positions[0].x;
+        assert!((held_x - bounds.maxs.x).abs() < 0.001);
+
+        // and it leaves from where it was drawn
+        state.launch_held();
+        assert_eq!(state.balls[0].pos.x, held_x);
+    }
+
+    #[test]
+    fn test_events() {
+        let level = Level::parse("row 4:score:hp=1:score=50").unwrap();
+        let mut state = State::from_level(0, level);
+        let (id, block) = state.blocks.iter().next().unwrap();
+        let target = block.rect.mins + block.rect.dims() * 0.5;
+        let kind = block.kind;
+
+        state.update(DT, Input { paddle_dir: 0, serve: true });
+        assert!(matches!(state.events(), [Event::BallServed { .. }]));
+
+        // send the ball straight up into the block
+        state.balls[0].pos.x = target.x;
+        state.balls[0].vel = V2::new(0., state.balls[0].speed);
+        while state.update(DT, Input { paddle_dir: 0, serve: false }) == Status::Playing {
+            let broken = state.events().iter().any(|event| match event {
+                Event::BlockBroken { id: broken, score, .. } => *broken == id && *score == 50,
+                _ => false,
+            });
+
+            if broken {
+                assert!(!state.blocks.contains(id));
+                assert_eq!(state.frame(0.).blocks_revision, 1);
+                assert!(state.frame(0.).particles.into_iter()
+                    .any(|particle| particle.kind == ParticleKind::Debris { kind }));
+                break;
+            }
+        }
+
+        // and it comes back down to bank the combo on the paddle
+        state.balls[0].pos.x = state.paddle_x;
+        loop {
+            state.update(DT, Input { paddle_dir: 0, serve: false });
+            if state.events().contains(&Event::ComboEnded { value: 50, forfeit: false }) {
+                break;
+            }
         }
     }
 }
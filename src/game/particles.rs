@@ -0,0 +1,175 @@
+
+use {
+    super::*,
+    rand::Rng,
+};
+
+// Particles are purely for show: they have their own random numbers and nothing in
+// the game ever looks at them, so replays play out the same with or without them.
+
+// Keeps the particles off the random numbers the pickups draw from the same seed.
+const SEED_MIX: u64 = 0x9e37_79b9_7f4a_7c15;
+
+const MAX_PARTICLES: usize = 1024;
+const GRAVITY:       f32   = -900.;
+const SPARK_DRAG:    f32   = 4.;
+
+const DEBRIS_PER_STEP: i32 = 3;
+const DEBRIS_SPEED:    f32 = 160.;
+const DEBRIS_LIFETIME: f32 = 1.2;
+const DEBRIS_SIZE:     f32 = 4.;
+
+const SPARKS:         usize = 8;
+const SPARK_SPEED:    f32   = 240.;
+const SPARK_SPREAD:   f32   = 1.2;
+const SPARK_LIFETIME: f32   = 0.25;
+const SPARK_SIZE:     f32   = 2.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParticleKind {
+    // a shard of a block, coloured as a block of this kind
+    Debris { kind: block::Kind },
+    Spark,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Particle {
+    pub kind:     ParticleKind,
+    pub size:     f32,
+    pub angle:    f32,
+    pos:          P2,
+    prev_pos:     P2,
+    vel:          V2,
+    spin:         f32,
+    age:          f32,
+    lifetime:     f32,
+}
+
+impl Particle {
+    pub fn position(&self, alpha: f32) -> P2 {
+        self.prev_pos.coords.lerp(&self.pos.coords, alpha).into()
+    }
+
+    // Goes from 1 when spawned down to 0 as it expires.
+    pub fn fade(&self) -> f32 {
+        1. - self.age / self.lifetime
+    }
+}
+
+pub struct Particles {
+    rng:       Pcg32Basic,
+    particles: Vec<Particle>,
+}
+
+impl<'a> IntoIterator for &'a Particles {
+    type Item = &'a Particle;
+    type IntoIter = std::slice::Iter<'a, Particle>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.particles.iter()
+    }
+}
+
+impl Particles {
+    pub fn new(seed: u64) -> Particles {
+        let rng = Pcg32Basic::seed_from_u64(seed ^ SEED_MIX);
+        Particles { rng, particles: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    fn spawn(&mut self, kind: ParticleKind, pos: P2, vel: V2, size: f32, lifetime: f32) {
+        if self.particles.len() == MAX_PARTICLES {
+            return;
+        }
+
+        let angle = self.rng.gen_range(0., std::f32::consts::PI);
+        let spin  = self.rng.gen_range(-10., 10.);
+        let lifetime = lifetime * self.rng.gen_range(0.6, 1.);
+        self.particles.push(Particle {
+            kind, size, angle,
+            pos, prev_pos: pos, vel, spin,
+            age: 0., lifetime,
+        });
+    }
+
+    fn debris(&mut self, rect: Rect, kind: block::Kind) {
+        let steps = (rect.width() / SPLIT_STEP as f32).ceil().max(1.) as i32;
+        for _ in 0 .. steps * DEBRIS_PER_STEP {
+            let pos = P2::new(
+                self.rng.gen_range(rect.mins.x, rect.maxs.x),
+                self.rng.gen_range(rect.mins.y, rect.maxs.y),
+            );
+
+            let vel = V2::new(
+                self.rng.gen_range(-1., 1.),
+                self.rng.gen_range(-0.5, 1.)
+            ) * DEBRIS_SPEED;
+
+            self.spawn(ParticleKind::Debris { kind }, pos, vel, DEBRIS_SIZE, DEBRIS_LIFETIME);
+        }
+    }
+
+    fn sparks(&mut self, pos: P2, normal: V2) {
+        for _ in 0 .. SPARKS {
+            let angle = self.rng.gen_range(-SPARK_SPREAD, SPARK_SPREAD);
+            let speed = self.rng.gen_range(0.3, 1.) * SPARK_SPEED;
+            let vel = rotate(normal, angle) * speed;
+            self.spawn(ParticleKind::Spark, pos, vel, SPARK_SIZE, SPARK_LIFETIME);
+        }
+    }
+
+    pub fn react(&mut self, events: &[Event]) {
+        for event in events {
+            match *event {
+                Event::BlockBroken { rect, kind, .. } => {
+                    self.debris(rect, kind);
+                }
+
+                Event::BlockDamaged { position, kind, .. } => {
+                    let rect = Rect::new_square_centered(SPLIT_STEP as f32).at(position);
+                    self.debris(rect, kind);
+                }
+
+                Event::BlockExploded { rect, .. } => {
+                    let center = rect.mins + rect.dims() * 0.5;
+                    for quarter in 0 .. 4 {
+                        let normal = rotate(V2::new(1., 0.), quarter as f32 * std::f32::consts::FRAC_PI_2);
+                        self.sparks(center, normal);
+                    }
+                }
+
+                Event::PaddleHit { position } => {
+                    self.sparks(position, V2::new(0., 1.));
+                }
+
+                Event::WallHit { position, normal } => {
+                    self.sparks(position, normal);
+                }
+
+                _ => { }
+            }
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for particle in self.particles.iter_mut() {
+            particle.prev_pos = particle.pos;
+            particle.pos += particle.vel * dt;
+            particle.angle += particle.spin * dt;
+            particle.age += dt;
+
+            match particle.kind {
+                ParticleKind::Debris { .. } => { particle.vel.y += GRAVITY * dt; }
+                ParticleKind::Spark         => { particle.vel -= particle.vel * SPARK_DRAG * dt; }
+            }
+        }
+
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+    }
+}
@@ -11,6 +11,7 @@ use {
 
 const DROP_CHANCE: f32 = 0.5;
 const DROP_SPEED:  f32 = 300.0;
+const SPIN_SPEED:  f32 = 3.0;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PickupKind {
@@ -18,12 +19,17 @@ pub enum PickupKind {
     ExtraBall,
     Detonator,
     MultiBall,
+    WidePaddle,
+    NarrowPaddle,
+    Catch,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct Pickup {
     pub position: P2,
     pub kind:     PickupKind,
+    // only for show; turns as it falls
+    pub angle:    f32,
 }
 
 pub struct Pickups {
@@ -47,26 +53,31 @@ impl Pickups {
         Pickups { rng, pickups }
     }
 
-    pub fn block_broken(&mut self, block: Block) {
+    // Returns the pickup dropped, if any.
+    pub fn block_broken(&mut self, block: Block) -> Option<Pickup> {
         if self.rng.gen::<f32>() >= DROP_CHANCE {
-            return;
+            return None;
         }
 
         use PickupKind::*;
         let kind = match self.rng.gen::<f32>() {
-            x if x < 0.7 => {
+            x if x < 0.6  => {
                 let amount = (self.rng.gen::<f32>() * 100.) as i32 * 10;
                 Bonus(amount)
             },
-            x if x < 0.8 => ExtraBall,
-            x if x < 0.9 => Detonator,
-            _            => MultiBall,
+            x if x < 0.67 => ExtraBall,
+            x if x < 0.74 => Detonator,
+            x if x < 0.81 => MultiBall,
+            x if x < 0.88 => WidePaddle,
+            x if x < 0.94 => NarrowPaddle,
+            _             => Catch,
         };
 
         let position = block.rect.mins + 0.5 * block.rect.dims();
 
-        let pickup = Pickup { position, kind };
+        let pickup = Pickup { position, kind, angle: 0. };
         self.pickups.push(pickup);
+        Some(pickup)
     }
 
     pub fn update(&mut self, dt: f32, paddle_rect: Rect, floor_level: f32) -> Vec<PickupKind> {
@@ -75,13 +86,14 @@ impl Pickups {
 
         for pickup in self.pickups.iter_mut() {
             pickup.position.y -= dt * DROP_SPEED;
+            pickup.angle += dt * SPIN_SPEED;
         }
 
         let mut collected = Vec::new();
         self.pickups.retain(|pickup| {
             let hit = paddle_rect.contains(pickup.position);
             if hit { collected.push(pickup.kind); }
-            !hit && pickup.position.y > 0.
+            !hit && pickup.position.y > floor_level
         });
 
         collected
@@ -0,0 +1,385 @@
+
+use {
+    super::{
+        campaign::{CampaignError, Progress},
+        level::{pieces, LoadError, ParseError},
+        *,
+    },
+    std::{
+        io::{self, Write},
+        path::Path,
+    },
+};
+
+// Replays are plain text:
+//
+//   breakout-replay 2
+//   seed <campaign seed>
+//   level seed <seed>           one per campaign level, in order, either generated
+//   level file <path>           or authored
+//   | <text>                    the authored level's text, a line at a time
+//   inputs <run>...             the per-tick input stream, split over many lines
+//
+// An authored level is played from the text in the replay, and only read from
+// its file when the replay doesn't have it.
+//
+// Each run is a tick count followed by one letter for the input held during
+// those ticks: `l`, `n` or `r` for the paddle direction, upper case while serve
+// is held. `180n 12R` is one second of idling followed by serving to the right.
+
+const MAGIC:         &str  = "breakout-replay 2";
+const RUNS_PER_LINE: usize = 16;
+
+fn encode(input: Input) -> char {
+    let c = match input.paddle_dir {
+        d if d < 0 => 'l',
+        0          => 'n',
+        _          => 'r',
+    };
+
+    if input.serve { c.to_ascii_uppercase() }
+    else           { c }
+}
+
+fn decode(c: char) -> Option<Input> {
+    let paddle_dir = match c.to_ascii_lowercase() {
+        'l' => -1,
+        'n' =>  0,
+        'r' =>  1,
+        _   => { return None; }
+    };
+
+    Some(Input { paddle_dir, serve: c.is_ascii_uppercase() })
+}
+
+fn parse_run(line: usize, offset: usize, run: &str) -> Result<(u32, Input), ParseError> {
+    let split = run.len() - run.chars().last().map_or(0, char::len_utf8);
+    let count = run[.. split].parse::<u32>().ok().filter(|count| *count > 0);
+    let input = run[split ..].chars().next().and_then(decode);
+
+    match (count, input) {
+        (Some(count), Some(input)) => Ok((count, input)),
+        _ => Err(ParseError {
+            line:    line + 1,
+            column:  offset + 1,
+            message: format!("expected `<ticks><l|n|r>`, found `{}`", run),
+        }),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Replay {
+    pub seed:   u64,
+    pub levels: Vec<LevelSource>,
+    runs:       Vec<(u32, Input)>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, levels: &[LevelSource]) -> Replay {
+        Replay { seed, levels: levels.to_vec(), runs: Vec::new() }
+    }
+
+    pub fn record(&mut self, input: Input) {
+        match self.runs.last_mut() {
+            Some((count, last)) if *last == input => { *count += 1; }
+            _ => { self.runs.push((1, input)); }
+        }
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.runs.iter().map(|(count, _)| *count as u64).sum()
+    }
+
+    pub fn playback(&self) -> Playback {
+        Playback { runs: self.runs.clone().into_iter(), current: None }
+    }
+
+    pub fn campaign(&self) -> Result<Campaign, CampaignError> {
+        Campaign::new(self.seed, &self.levels)
+    }
+
+    // Replays the whole input stream, stopping early if the campaign ends.
+    pub fn run(&self, dt: f32) -> Result<(Campaign, Progress), CampaignError> {
+        let mut campaign = self.campaign()?;
+        let mut progress = Progress::Playing;
+
+        for input in self.playback() {
+            progress = campaign.update(dt, input);
+            if progress.is_over() {
+                break;
+            }
+        }
+
+        Ok((campaign, progress))
+    }
+
+    pub fn write(&self, mut out: impl Write) -> io::Result<()> {
+        writeln!(out, "{}", MAGIC)?;
+        writeln!(out, "seed {}", self.seed)?;
+
+        for level in self.levels.iter() {
+            match level {
+                LevelSource::Procedural(seed) => writeln!(out, "level seed {}", seed)?,
+                LevelSource::File(path)       => writeln!(out, "level file {}", path.display())?,
+                LevelSource::Text { path, text } => {
+                    writeln!(out, "level file {}", path.display())?;
+                    for line in text.lines() {
+                        writeln!(out, "| {}", line)?;
+                    }
+                }
+            }
+        }
+
+        for line in self.runs.chunks(RUNS_PER_LINE) {
+            write!(out, "inputs")?;
+            for (count, input) in line {
+                write!(out, " {}{}", count, encode(*input))?;
+            }
+            writeln!(out)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.write(io::BufWriter::new(file))
+    }
+
+    pub fn parse(text: &str) -> Result<Replay, ParseError> {
+        let error = |line: usize, offset: usize, message: String| {
+            Err(ParseError { line: line + 1, column: offset + 1, message })
+        };
+
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, line)) if line.trim() == MAGIC => { }
+            _ => { return error(0, 0, format!("expected `{}`", MAGIC)); }
+        }
+
+        let mut seed   = None;
+        let mut levels = Vec::new();
+        let mut runs   = Vec::new();
+
+        for (index, line) in lines {
+            if let Some(text_line) = line.strip_prefix('|') {
+                let text = match levels.last_mut() {
+                    Some(LevelSource::Text { text, .. }) => text,
+                    _ => { return error(index, 0, "level text without a `level file` before it".to_owned()); }
+                };
+
+                text.push_str(text_line.strip_prefix(' ').unwrap_or(text_line));
+                text.push('\n');
+                continue;
+            }
+
+            let words = pieces(line, 0, char::is_whitespace);
+            let (offset, directive) = match words.first() {
+                Some(word) => *word,
+                None       => { continue; }
+            };
+
+            let rest = line[offset + directive.len() ..].trim();
+            match directive {
+                "seed" => match rest.parse() {
+                    Ok(value) => { seed = Some(value); }
+                    Err(_) => { return error(index, offset, format!("expected a seed, found `{}`", rest)); }
+                },
+
+                "level" => {
+                    let level = match (words.get(1), words.get(2)) {
+                        (Some((_, "seed")), Some(&(seed_offset, seed))) => match seed.parse() {
+                            Ok(seed) => LevelSource::Procedural(seed),
+                            Err(_) => { return error(index, seed_offset, format!("expected a seed, found `{}`", seed)); }
+                        },
+
+                        (Some((_, "file")), Some(&(path_offset, _))) => LevelSource::Text {
+                            path: line[path_offset ..].trim_end().into(),
+                            text: String::new(),
+                        },
+
+                        _ => { return error(index, offset, "expected `level seed <seed>` or `level file <path>`".to_owned()); }
+                    };
+                    levels.push(level);
+                }
+
+                "inputs" => for &(offset, run) in &words[1..] {
+                    runs.push(parse_run(index, offset, run)?);
+                },
+
+                _ => { return error(index, offset, format!("unexpected `{}`", directive)); }
+            }
+        }
+
+        let seed = match seed {
+            Some(seed) => seed,
+            None => { return error(0, 0, "replay has no seed".to_owned()); }
+        };
+
+        if levels.is_empty() {
+            return error(0, 0, "replay has no levels".to_owned());
+        }
+
+        // a level file without its text is read from the file itself
+        let levels = levels.into_iter()
+            .map(|level| match level {
+                LevelSource::Text { path, text } if text.is_empty() => LevelSource::File(path),
+                level => level,
+            })
+            .collect();
+
+        Ok(Replay { seed, levels, runs })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Replay, LoadError> {
+        let text = std::fs::read_to_string(path).map_err(LoadError::Io)?;
+        Replay::parse(&text).map_err(LoadError::Parse)
+    }
+}
+
+// A script is a bare list of runs, with `#` comments, that repeats forever.
+#[derive(Clone, Debug)]
+pub struct Script {
+    runs: Vec<(u32, Input)>,
+}
+
+impl Script {
+    pub fn parse(text: &str) -> Result<Script, ParseError> {
+        let mut runs = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(comment) => &line[.. comment],
+                None          => line,
+            };
+
+            for (offset, run) in pieces(line, 0, char::is_whitespace) {
+                runs.push(parse_run(index, offset, run)?);
+            }
+        }
+
+        if runs.is_empty() {
+            return Err(ParseError { line: 1, column: 1, message: "script has no inputs".to_owned() });
+        }
+
+        Ok(Script { runs })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Script, LoadError> {
+        let text = std::fs::read_to_string(path).map_err(LoadError::Io)?;
+        Script::parse(&text).map_err(LoadError::Parse)
+    }
+
+    pub fn inputs<'a> (&'a self) -> impl Iterator<Item = Input> + 'a {
+        self.runs.iter()
+            .flat_map(|(count, input)| std::iter::repeat_n(*input, *count as usize))
+            .cycle()
+    }
+}
+
+pub struct Playback {
+    runs:    std::vec::IntoIter<(u32, Input)>,
+    current: Option<(u32, Input)>,
+}
+
+impl Iterator for Playback {
+    type Item = Input;
+
+    fn next(&mut self) -> Option<Input> {
+        loop {
+            match &mut self.current {
+                Some((count, input)) if *count > 0 => {
+                    *count -= 1;
+                    return Some(*input);
+                }
+
+                _ => {
+                    self.current = Some(self.runs.next()?);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let levels = [
+            LevelSource::Procedural(7),
+            LevelSource::File("123".into()),
+            LevelSource::Text { path: "a level.txt".into(), text: "name A\n\n| row 4:score\n".into() },
+        ];
+        let mut replay = Replay::new(42, &levels);
+
+        let inputs = [(0, false), (0, false), (1, true), (-1, false), (-1, false), (0, false)];
+        for &(paddle_dir, serve) in inputs.iter() {
+            replay.record(Input { paddle_dir, serve });
+        }
+
+        let mut text = Vec::new();
+        replay.write(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("inputs 2n 1R 2l 1n\n"));
+
+        let parsed = Replay::parse(&text).unwrap();
+        assert_eq!(parsed.seed, 42);
+        assert_eq!(parsed.ticks(), 6);
+        assert!(matches!(&parsed.levels[..], [
+            LevelSource::Procedural(7),
+            LevelSource::File(number),
+            LevelSource::Text { path, text },
+        ] if number.to_str() == Some("123")
+            && path.to_str() == Some("a level.txt")
+            && text == "name A\n\n| row 4:score\n"));
+
+        let played: Vec<_> = parsed.playback()
+            .map(|input| (input.paddle_dir, input.serve))
+            .collect();
+        assert_eq!(played, inputs);
+    }
+
+    #[test]
+    fn test_level_files_go_in_the_replay() {
+        let campaign = Campaign::new(1, &[LevelSource::File("levels/stripes.txt".into())]).unwrap();
+        let replay = Replay::new(1, campaign.sources());
+
+        let mut text = Vec::new();
+        replay.write(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("level file levels/stripes.txt\n| # An example"));
+        assert!(text.contains("\n| name Stripes\n"));
+
+        let parsed = Replay::parse(&text).unwrap();
+        let level = Level::load("levels/stripes.txt").unwrap();
+        assert_eq!(parsed.campaign().unwrap().frame(0.).blocks.len(), level.blocks.len());
+
+        let err = Replay::parse(&format!("{}\nseed 1\nlevel seed 2\n| row 4:score", MAGIC)).unwrap_err();
+        assert_eq!((err.line, err.column), (4, 1));
+    }
+
+    #[test]
+    fn test_playback_is_deterministic() {
+        let levels = [LevelSource::Procedural(1234)];
+        let mut replay = Replay::new(99, &levels);
+        let mut campaign = replay.campaign().unwrap();
+
+        for tick in 0 .. 20_000 {
+            let paddle_dir = [-1, 0, 1, 0][(tick / 97) % 4];
+            let input = Input { paddle_dir, serve: tick % 500 < 3 };
+            replay.record(input);
+            if campaign.update(DT, input).is_over() {
+                break;
+            }
+        }
+
+        let (replayed, _) = replay.run(DT).unwrap();
+        let (a, b) = (campaign.frame(0.), replayed.frame(0.));
+        assert_eq!(a.scoring.score, b.scoring.score);
+        assert_eq!(a.scoring.penalties, b.scoring.penalties);
+        assert_eq!(a.lives, b.lives);
+        assert_eq!(a.paddle_pos, b.paddle_pos);
+        assert_eq!(a.ball_positions, b.ball_positions);
+    }
+}
@@ -1,59 +1,115 @@
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BonusMode {
+    // bonuses ride on the running combo and are forfeit if it ends on the floor
+    Combo,
+    // bonuses go straight into the score
+    Bank,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Scoring {
     pub score:            i64,
     pub combo_score:      i64,
+    pub combo_bonus:      i64,
     pub combo_multiplier: f64,
     pub combo_max:        i64,
     pub penalties:        i64,
+    pub bonus:            i64,
+    pub bonus_mode:       BonusMode,
 }
 
 impl Scoring {
-    pub fn new() -> Scoring {
+    pub fn new(bonus_mode: BonusMode) -> Scoring {
         Scoring {
             score:            0,
             combo_score:      0,
+            combo_bonus:      0,
             combo_multiplier: 1.,
             combo_max:        0,
             penalties:        0,
+            bonus:            0,
+            bonus_mode,
         }
     }
 
-    fn end_combo(&mut self) -> i64 {
+    fn end_combo(&mut self) -> (i64, i64) {
         let combo = self.combo_score;
+        let bonus = self.combo_bonus;
         self.combo_score = 0;
+        self.combo_bonus = 0;
         self.combo_max = self.combo_max.max(combo);
         self.combo_multiplier = 1.;
-        combo
+        (combo, bonus)
     }
 
-    pub fn hit_floor(&mut self) {
-        let combo = self.end_combo();
+    // Returns the combo lost.
+    pub fn hit_floor(&mut self) -> i64 {
+        let (combo, _) = self.end_combo();
         self.score     -= combo;
         self.penalties += combo;
+        combo
     }
 
-    pub fn hit_paddle(&mut self) {
-        let combo = self.end_combo();
-        self.score += combo;
+    // Returns the combo banked, bonus included.
+    pub fn hit_paddle(&mut self) -> i64 {
+        let (combo, bonus) = self.end_combo();
+        self.score += combo + bonus;
+        self.bonus += bonus;
+        combo + bonus
+    }
+
+    pub fn bonus_collected(&mut self, amount: i64) {
+        match self.bonus_mode {
+            BonusMode::Combo => {
+                self.combo_bonus += amount;
+            }
+
+            BonusMode::Bank => {
+                self.score += amount;
+                self.bonus += amount;
+            }
+        }
     }
 
-    pub fn block_broken(&mut self, block_score: i64) {
-        let score = self.combo_multiplier * block_score as f64;
-        self.combo_score += score.round() as i64;
+    // Returns the score awarded, multiplier included.
+    pub fn block_broken(&mut self, block_score: i64) -> i64 {
+        let score = (self.combo_multiplier * block_score as f64).round() as i64;
+        self.combo_score += score;
         self.combo_multiplier += 1.;
+        score
     }
 
     pub fn block_damaged(&mut self) {
         self.combo_multiplier += 0.1;
     }
 
+    // Each level says how its own bonuses go.
+    pub fn next_level(&self, bonus_mode: BonusMode) -> Scoring {
+        Scoring { combo_max: 0, bonus_mode, ..*self }
+    }
+
+    pub fn since(&self, start: &Scoring) -> Scoring {
+        Scoring {
+            score:     self.score     - start.score,
+            penalties: self.penalties - start.penalties,
+            bonus:     self.bonus     - start.bonus,
+            ..*self
+        }
+    }
+
     pub fn no_combo(&self) -> bool {
-        self.combo_score == 0
+        self.combo_score == 0 && self.combo_bonus == 0
     }
 
+    // Bonuses don't count towards the rank, and earning nothing at all is the worst.
     pub fn rank(&self) -> Rank {
-        match ((self.penalties as f64 / self.score as f64) * 1000.).trunc() as i64 {
+        let earned = self.score - self.bonus;
+        if earned <= 0 {
+            return Rank::F;
+        }
+
+        match ((self.penalties as f64 / earned as f64) * 1000.).trunc() as i64 {
                 0 ..=   5 => Rank::S,
                 6 ..=  25 => Rank::A,
                 26 ..=  50 => Rank::B,
@@ -91,3 +147,51 @@ impl std::fmt::Display for Rank {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combo_bonuses_bank_on_the_paddle_or_go_on_the_floor() {
+        let mut scoring = Scoring::new(BonusMode::Combo);
+        assert_eq!(scoring.block_broken(10), 10);
+        assert_eq!(scoring.block_broken(10), 20);
+        scoring.bonus_collected(100);
+        assert_eq!(scoring.score, 0);
+        assert_eq!(scoring.hit_paddle(), 130);
+        assert_eq!((scoring.score, scoring.bonus, scoring.combo_max), (130, 100, 30));
+
+        // the bonus riding on a combo that ends on the floor is lost with it
+        scoring.block_broken(10);
+        scoring.bonus_collected(50);
+        assert_eq!(scoring.hit_floor(), 10);
+        assert_eq!((scoring.score, scoring.bonus, scoring.penalties), (120, 100, 10));
+        assert!(scoring.no_combo());
+    }
+
+    #[test]
+    fn test_banked_bonuses_are_safe() {
+        let mut scoring = Scoring::new(BonusMode::Bank);
+        scoring.block_broken(10);
+        scoring.bonus_collected(100);
+        assert_eq!((scoring.score, scoring.bonus), (100, 100));
+        assert_eq!(scoring.hit_floor(), 10);
+        assert_eq!((scoring.score, scoring.bonus, scoring.penalties), (90, 100, 10));
+    }
+
+    #[test]
+    fn test_rank_leaves_out_bonuses() {
+        let mut scoring = Scoring::new(BonusMode::Bank);
+        assert_eq!(scoring.rank(), Rank::F);
+
+        // counting the bonus would halve the penalty ratio and make this an S
+        scoring.score     = 2000;
+        scoring.bonus     = 1000;
+        scoring.penalties = 6;
+        assert_eq!(scoring.rank(), Rank::A);
+
+        scoring.bonus = 2000;
+        assert_eq!(scoring.rank(), Rank::F);
+    }
+}
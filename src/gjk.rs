@@ -1,8 +1,27 @@
 
+use {
+    crate::{
+        collider::Collision,
+        math::*,
+    },
+};
+
+const MAX_ITERATIONS: usize = 32;
+const TOLERANCE:      f32   = 0.001;
+
+// Number of directions sampled when estimating the way out of an overlap.
+const OVERLAP_SAMPLES: usize = 32;
+
 pub trait Convex {
     fn furthest_along(&self, direction: V2) -> P2;
 }
 
+impl<C: Convex> Convex for &C {
+    fn furthest_along(&self, direction: V2) -> P2 {
+        (*self).furthest_along(direction)
+    }
+}
+
 impl Convex for Rect {
     fn furthest_along(&self, direction: V2) -> P2 {
         let x = if direction.x < 0. { self.mins.x }
@@ -13,13 +32,271 @@ impl Convex for Rect {
     }
 }
 
-fn gjk_relative(path: Segment, moving: impl Convex, fixed: impl Convex) {
+impl Convex for Circle {
+    fn furthest_along(&self, direction: V2) -> P2 {
+        let length = direction.norm();
+        if length < 1e-12 { self.center }
+        else              { self.center + direction * (self.radius / length) }
+    }
+}
+
+// A point of the Minkowski difference `a - b`, along with the points of `a` and `b`
+// that produced it.
+#[derive(Clone, Copy, Debug)]
+struct Support {
+    v: V2,
+    a: P2,
+    b: P2,
+}
+
+fn support(a: &impl Convex, offset: V2, b: &impl Convex, direction: V2) -> Support {
+    let a = a.furthest_along(direction) + offset;
+    let b = b.furthest_along(-direction);
+    Support { v: a - b, a, b }
+}
+
+// Closest point to the origin on the segment `p`-`q`, as a weight on `q`.
+fn segment_weight(p: V2, q: V2) -> f32 {
+    let edge = q - p;
+    let length = edge.norm_squared();
+    if length < 1e-12 { 0. }
+    else              { (-p.dot(&edge) / length).clamp(0., 1.) }
+}
+
+// Reduces `simplex` to the feature closest to the origin, returning the closest point
+// and its witness points on each shape. Returns `None` if the simplex holds the origin.
+fn reduce(simplex: &mut Vec<Support>) -> Option<(V2, P2, P2)> {
+    match simplex.len() {
+        1 => {
+            let s = simplex[0];
+            Some((s.v, s.a, s.b))
+        }
+
+        2 => {
+            let (p, q) = (simplex[0], simplex[1]);
+            let t = segment_weight(p.v, q.v);
+            if      t <= 0. { simplex.remove(1); }
+            else if t >= 1. { simplex.remove(0); }
+
+            let v = p.v.lerp(&q.v, t);
+            let a = p.a.coords.lerp(&q.a.coords, t).into();
+            let b = p.b.coords.lerp(&q.b.coords, t).into();
+            Some((v, a, b))
+        }
+
+        _ => {
+            let cross = |u: V2, w: V2| u.x * w.y - u.y * w.x;
+            let (p, q, r) = (simplex[0].v, simplex[1].v, simplex[2].v);
+            let signs = [cross(q - p, -p), cross(r - q, -q), cross(p - r, -r)];
+            if signs.iter().all(|s| *s >= 0.) || signs.iter().all(|s| *s <= 0.) {
+                return None;
+            }
+
+            let closest_edge = [(0, 1), (1, 2), (2, 0)].iter()
+                .map(|&(i, j)| {
+                    let t = segment_weight(simplex[i].v, simplex[j].v);
+                    let v = simplex[i].v.lerp(&simplex[j].v, t);
+                    (i, j, v.norm_squared())
+                })
+                .min_by_key(|(_, _, distance)| OrdF32(*distance))
+                .unwrap();
+
+            let (i, j, _) = closest_edge;
+            *simplex = vec![simplex[i], simplex[j]];
+            reduce(simplex)
+        }
+    }
+}
+
+// Distance between `a` translated by `offset` and `b`, with the closest points on each.
+// Returns `None` if they overlap; shapes within `TOLERANCE` count as touching.
+fn closest_points(a: &impl Convex, offset: V2, b: &impl Convex) -> Option<(f32, P2, P2)> {
+    let mut simplex = vec![support(a, offset, b, V2::new(1., 0.))];
+
+    let mut best = None;
+    for _ in 0 .. MAX_ITERATIONS {
+        let (v, pa, pb) = reduce(&mut simplex)?;
+        let distance = v.norm();
+        best = Some((distance, pa, pb));
+
+        if distance < TOLERANCE {
+            break;
+        }
+
+        let next = support(a, offset, b, -v);
+        let progress = distance - next.v.dot(&v) / distance;
+        if progress < TOLERANCE * 0.1 {
+            break;
+        }
+
+        simplex.push(next);
+    }
+
+    best
+}
+
+// Estimates the shortest way out of an overlap by sampling directions around the circle.
+fn separation(a: &impl Convex, offset: V2, b: &impl Convex) -> (V2, P2) {
+    (0 .. OVERLAP_SAMPLES)
+        .map(|i| {
+            let angle = i as f32 * std::f32::consts::PI * 2. / OVERLAP_SAMPLES as f32;
+            let normal = V2::new(angle.cos(), angle.sin());
+            let depth = -support(a, offset, b, -normal).v.dot(&normal);
+            (depth, normal)
+        })
+        .min_by_key(|(depth, _)| OrdF32(*depth))
+        .map(|(_, normal)| (normal, b.furthest_along(normal)))
+        .unwrap()
+}
+
+// Sweeps `moving`, placed at the start of `path`, along it towards `fixed`, which sits
+// at the origin. The collision normal points from `fixed` towards `moving`.
+fn gjk_relative(path: Segment, moving: impl Convex, fixed: impl Convex) -> Option<Collision> {
+    let start = path.source().coords;
+    let stride = path.stride();
+
+    let mut param = 0.;
+    let mut normal: Option<V2> = None;
+    for _ in 0 .. MAX_ITERATIONS {
+        let offset = start + stride * param;
+        let (distance, pa, pb) = match closest_points(&moving, offset, &fixed) {
+            Some(closest) => closest,
+
+            // already overlapping; nothing to report if it's on its way out
+            None if param == 0. => {
+                let (normal, point) = separation(&moving, offset, &fixed);
+                if stride.dot(&normal) > TOLERANCE {
+                    return None;
+                }
+                return Some(Collision { param, point, normal });
+            }
+
+            None => {
+                let normal = normal?;
+                let point = fixed.furthest_along(normal);
+                return Some(Collision { param, point, normal });
+            }
+        };
+
+        // when touching, the witness points are too close to give a direction
+        let n = match normal {
+            _ if distance > TOLERANCE * 0.5 => (pa - pb) / distance,
+            Some(normal) => normal,
+            None         => separation(&moving, offset, &fixed).0,
+        };
+
+        // everything of the difference lies beyond `distance` along `n`, so this
+        // much of the path is certainly clear
+        let closing = -stride.dot(&n);
+        if closing <= 0. {
+            return None;
+        }
+
+        if distance < TOLERANCE * 2. {
+            return Some(Collision { param, point: pb, normal: n });
+        }
+
+        normal = Some(n);
+        param += (distance - TOLERANCE) / closing;
+        if param > 1. {
+            return None;
+        }
+    }
+
+    None
 }
 
-fn gjk(
+pub fn gjk(
     path_a: Segment, convex_a: impl Convex,
     path_b: Segment, convex_b: impl Convex)
+    -> Option<Collision>
 {
-    gjk_relative(path_a.relative_to(path_b), convex_a, convex_b)
+    let mut collision = gjk_relative(path_a.relative_to(path_b), convex_a, convex_b)?;
+    collision.point += path_b.source().coords + path_b.stride() * collision.param;
+    Some(collision)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn still(at: P2) -> Segment {
+        Segment::new(at, V2::new(0., 0.))
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 0.01, "{} is not near {}", a, b);
+    }
+
+    #[test]
+    fn test_rect_head_on() {
+        let square = Rect::new_square_centered(2.);
+        let path = Segment::new(P2::new(-5., 0.), V2::new(10., 0.));
+        let hit = gjk(path, square, still(P2::new(0., 0.)), square).unwrap();
+
+        assert_near(hit.param, 0.3);
+        assert_near(hit.point.x, -1.);
+        assert_near(hit.normal.x, -1.);
+        assert_near(hit.normal.y,  0.);
+    }
+
+    #[test]
+    fn test_rect_both_moving() {
+        let square = Rect::new_square_centered(2.);
+        let path_a = Segment::new(P2::new(-5., 0.), V2::new( 6., 0.));
+        let path_b = Segment::new(P2::new( 5., 0.), V2::new(-6., 0.));
+        let hit = gjk(path_a, square, path_b, square).unwrap();
+
+        assert_near(hit.param, 2. / 3.);
+        assert_near(hit.point.x, 0.);
+        assert_near(hit.normal.x, -1.);
+    }
+
+    #[test]
+    fn test_rect_grazing() {
+        let square = Rect::new_square_centered(2.);
+        let fixed = still(P2::new(0., 0.));
+
+        let miss = Segment::new(P2::new(-5., 2.01), V2::new(10., 0.));
+        assert!(gjk(miss, square, fixed, square).is_none());
+
+        let clip = Segment::new(P2::new(-5., 1.99), V2::new(10., 0.));
+        let hit = gjk(clip, square, fixed, square).unwrap();
+        assert_near(hit.param, 0.3);
+        assert_near(hit.normal.x, -1.);
+
+        let away = Segment::new(P2::new(-5., 0.), V2::new(-10., 0.));
+        assert!(gjk(away, square, fixed, square).is_none());
+
+        let leaving = Segment::new(P2::new(-2., 0.), V2::new(-10., 0.));
+        assert!(gjk(leaving, square, fixed, square).is_none());
+    }
+
+    #[test]
+    fn test_circle_into_rect_corner() {
+        let square = Rect::new_square_centered(2.);
+        let path = Segment::new(P2::new(-5., -5.), V2::new(10., 10.));
+        let hit = gjk(path, Circle::new_centered(1.), still(P2::new(0., 0.)), square).unwrap();
+
+        let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+        assert_near(hit.param, (4. - diagonal) / 10.);
+        assert_near(hit.point.x, -1.);
+        assert_near(hit.point.y, -1.);
+        assert_near(hit.normal.x, -diagonal);
+        assert_near(hit.normal.y, -diagonal);
+    }
+
+    #[test]
+    fn test_rect_already_overlapping() {
+        let square = Rect::new_square_centered(2.);
+        let path = Segment::new(P2::new(0.5, 1.5), V2::new(-1., 0.));
+        let hit = gjk(path, square, still(P2::new(0., 0.)), square).unwrap();
+
+        assert_eq!(hit.param, 0.);
+        assert_near(hit.normal.x, 0.);
+        assert_near(hit.normal.y, 1.);
+
+        let leaving = Segment::new(P2::new(0.5, 1.5), V2::new(-1., 1.));
+        assert!(gjk(leaving, square, still(P2::new(0., 0.)), square).is_none());
+    }
+}
@@ -0,0 +1,6 @@
+pub mod block;
+pub mod collider;
+pub mod dilate;
+pub mod game;
+pub mod gjk;
+pub mod math;
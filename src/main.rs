@@ -1,14 +1,9 @@
 
-mod block;
-mod collider;
-mod dilate;
-mod game;
-mod math;
-
 use {
-    crate::{
-        block::Block,
+    breakout::{
+        block::{self, Block},
         dilate::Dilate,
+        game::{self, DT, FRAMERATE},
         math::*,
     },
     ggez::{
@@ -23,7 +18,7 @@ const KEY_LEFT : keyboard::KeyCode = keyboard::KeyCode::A;
 const KEY_RIGHT: keyboard::KeyCode = keyboard::KeyCode::D;
 const KEY_SERVE: keyboard::KeyCode = keyboard::KeyCode::Space;
 
-fn block_color(block: &Block) -> graphics::Color {
+fn hp_color(hp: i32) -> graphics::Color {
     use graphics::Color as C;
     const COLORS: &[graphics::Color] = &[
         C::new(0.0, 0.0, 0.1, 1.), // dark blue
@@ -39,38 +34,90 @@ fn block_color(block: &Block) -> graphics::Color {
         C::new(1.0, 1.0, 1.0, 1.), // white
     ];
 
-    match block.hp() {
-        Some(hp) => COLORS[(hp as usize - 1).min(COLORS.len() - 1)],
-        None     => C::new(0.0, 0.0, 0.0, 1.)
+    COLORS[(hp.max(1) as usize - 1).min(COLORS.len() - 1)]
+}
+
+fn graphics_rect(rect: Rect) -> graphics::Rect {
+    graphics::Rect::new(rect.mins.x, rect.mins.y, rect.width(), rect.height())
+}
+
+fn tint(color: graphics::Color, with: graphics::Color, amount: f32) -> graphics::Color {
+    let mix = |a: f32, b: f32| a + (b - a) * amount;
+    graphics::Color::new(mix(color.r, with.r), mix(color.g, with.g), mix(color.b, with.b), color.a)
+}
+
+// Special blocks keep the colour for their hp, washed over with one of their own.
+fn block_color(kind: block::Kind) -> graphics::Color {
+    use {block::Kind::*, graphics::Color as C};
+    match kind {
+        Invlunerable            => C::new(0.0, 0.0, 0.0, 1.),
+        Scoring { hp, .. }      => hp_color(hp),
+        Explosive { hp, .. }    => tint(hp_color(hp), C::new(1.0, 0.4, 0.0, 1.), 0.6),
+        Armored { hp, .. }      => tint(hp_color(hp), C::new(0.6, 0.7, 0.8, 1.), 0.5),
+        Regenerating { hp, .. } => tint(hp_color(hp), C::new(0.1, 0.9, 0.3, 1.), 0.5),
+        Switch { link }         => link_color(link),
+        Gate { link, open }     => {
+            let color = tint(link_color(link), C::new(0.0, 0.0, 0.0, 1.), 0.4);
+            if open { C { a: 0.25, ..color } }
+            else    { color }
+        }
     }
 }
 
-const FRAMERATE: u32 = 180;
-const DT:        f32 = 1. / FRAMERATE as f32;
+// Switches share a colour with the gates they work.
+fn link_color(link: u32) -> graphics::Color {
+    use graphics::Color as C;
+    const COLORS: &[graphics::Color] = &[
+        C::new(0.0, 0.8, 0.9, 1.), // cyan
+        C::new(0.9, 0.2, 0.8, 1.), // magenta
+        C::new(0.3, 0.9, 0.2, 1.), // green
+        C::new(0.3, 0.4, 1.0, 1.), // blue
+    ];
 
-struct Sprite<'ctx> {
-    pub mesh:  &'ctx graphics::Mesh,
+    COLORS[link as usize % COLORS.len()]
+}
+
+fn particle_color(kind: game::ParticleKind) -> graphics::Color {
+    match kind {
+        game::ParticleKind::Debris { kind } => block_color(kind),
+        game::ParticleKind::Spark           => graphics::Color::new(1.0, 0.9, 0.5, 1.),
+    }
+}
+
+struct Sprite<'a> {
+    pub mesh:  &'a graphics::Mesh,
     pub angle: f32,
 }
 
-impl<'ctx> Sprite<'ctx> {
-    fn new(mesh: &'ctx graphics::Mesh, angle: f32) -> Sprite {
+impl<'a> Sprite<'a> {
+    fn new(mesh: &'a graphics::Mesh, angle: f32) -> Sprite<'a> {
         Sprite { mesh, angle }
     }
 }
 
-struct App<'ctx> {
-    state: game::State,
+const CAMPAIGN_LENGTH: usize = 3;
 
-    font: graphics::Font,
+struct App {
+    campaign:   game::Campaign,
+    controller: Option<Box<dyn game::Controller>>,
+    recording:  Option<game::Replay>,
+
+    // the whole block field in one mesh, and the revision it was built from
+    blocks:          Option<graphics::Mesh>,
+    blocks_revision: Option<u64>,
+
+    font:  graphics::Font,
     bonus: graphics::Mesh,
-    heart: Sprite<'ctx>,
-    bomb:  Sprite<'ctx>,
-    multi: Sprite<'ctx>,
+    heart: graphics::Mesh,
+    bomb:  graphics::Mesh,
+    multi: graphics::Mesh,
+    wide:   graphics::Mesh,
+    narrow: graphics::Mesh,
+    catch:  graphics::Mesh,
 }
 
 impl App {
-    fn new(ctx: &mut Context, state: game::State) -> GameResult<App> {
+    fn new(ctx: &mut Context, campaign: game::Campaign) -> GameResult<App> {
         let font = graphics::Font::new(ctx, "/Signika-SemiBold.ttf")?;
 
         let heart_points = [
@@ -89,22 +136,113 @@ impl App {
             [1., 0., 0., 1.].into()
         )?;
 
-        let game = App { state, font, heart };
+        // an octagon, so the spin shows
+        let bonus_points: Vec<P2> = (0 .. 8)
+            .map(|i| {
+                let angle = i as f32 * std::f32::consts::PI / 4.;
+                P2::new(angle.cos(), angle.sin()) * 11.
+            })
+            .collect();
+
+        let bonus = graphics::Mesh::new_polygon(
+            ctx,
+            graphics::DrawMode::fill(),
+            &bonus_points,
+            [1.0, 0.8, 0.0, 1.].into()
+        )?;
+
+        let fill = graphics::DrawMode::fill();
+
+        // body, fuse and spark
+        let bomb = graphics::MeshBuilder::new()
+            .circle(fill, P2::new(0., -1.), 6., 0.25, [0.4, 0.4, 0.45, 1.].into())
+            .rectangle(fill, graphics_rect(Rect::new(P2::new(-1., 4.), P2::new(1., 8.))), [0.9, 0.7, 0.5, 1.].into())
+            .circle(fill, P2::new(0., 8.), 2., 0.25, [1.0, 0.3, 0.0, 1.].into())
+            .build(ctx)?;
+
+        let mut multi = graphics::MeshBuilder::new();
+        for i in 0 .. 3 {
+            let angle = std::f32::consts::FRAC_PI_2 + i as f32 * std::f32::consts::PI * 2. / 3.;
+            let center = P2::new(angle.cos(), angle.sin()) * 4.;
+            multi.circle(fill, center, 3., 0.25, [1.0, 0.5, 0.0, 1.].into());
+        }
+        let multi = multi.build(ctx)?;
+
+        // a bar with arrows pointing out for wider, or in for narrower
+        let bar = |ctx: &mut Context, half: f32, tip: f32| {
+            let color = [0.9, 0.9, 1.0, 1.].into();
+            graphics::MeshBuilder::new()
+                .rectangle(fill, graphics_rect(Rect::new(P2::new(-half, -2.), P2::new(half, 2.))), color)
+                .triangles(&[
+                    P2::new( half, -5.), P2::new( half,  5.), P2::new( tip, 0.),
+                    P2::new(-half, -5.), P2::new(-tip,  0.), P2::new(-half,  5.),
+                ], color)?
+                .build(ctx)
+        };
+
+        let wide   = bar(ctx, 6.,  11.)?;
+        let narrow = bar(ctx, 11., 6.)?;
+
+        // a cup to catch the ball in
+        let cup_color = [0.4, 0.8, 1.0, 1.].into();
+        let catch = graphics::MeshBuilder::new()
+            .rectangle(fill, graphics_rect(Rect::new(P2::new(-7., -6.), P2::new(7., -3.))), cup_color)
+            .rectangle(fill, graphics_rect(Rect::new(P2::new(-7., -3.), P2::new(-4., 6.))), cup_color)
+            .rectangle(fill, graphics_rect(Rect::new(P2::new( 4., -3.), P2::new( 7., 6.))), cup_color)
+            .build(ctx)?;
+
+        let game = App {
+            campaign, controller: None, recording: None,
+            blocks: None, blocks_revision: None,
+            font, bonus, heart, bomb, multi, wide, narrow, catch,
+        };
         Ok(game)
     }
 
-    fn pickup_sprite(&self, kind: game::PickupKind) -> Sprite {
+    fn pickup_sprite(&self, pickup: &game::Pickup) -> Sprite<'_> {
         use game::PickupKind::*;
-        match kind {
-            Bonus(amount) => bonus_sprite(amount),
-            ExtraBall => self.heart,
-            Detonator => Sprite::new(15., 0., [1.0, 0.0, 0.0, 1.].into()),
-            MultiBall => Sprite::new(15., 0., [0.0, 1.0, 0.0, 1.].into()),
-        }
+        let mesh = match pickup.kind {
+            Bonus(_)     => &self.bonus,
+            ExtraBall    => &self.heart,
+            Detonator    => &self.bomb,
+            MultiBall    => &self.multi,
+            WidePaddle   => &self.wide,
+            NarrowPaddle => &self.narrow,
+            Catch        => &self.catch,
+        };
+
+        Sprite::new(mesh, pickup.angle)
+    }
+
+    // The amount is drawn over the sprite, and stays upright as it spins.
+    fn bonus_text(&self, amount: i32) -> graphics::Text {
+        let mut text = graphics::Text::new(amount.to_string());
+        text.set_font(self.font, graphics::Scale::uniform(11.));
+        text
     }
 }
 
-fn bonus_sprite(amount: i32) -> Sprite {
+// The blocks that stay put, or `None` once there are none left, as ggez won't build
+// an empty mesh.
+fn blocks_mesh(ctx: &mut Context, blocks: &game::Arena<Block>) -> GameResult<Option<graphics::Mesh>> {
+    let still: Vec<&Block> = blocks.values()
+        .filter(|block| !block.is_moving())
+        .collect();
+
+    if still.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = graphics::MeshBuilder::new();
+    for block in still {
+        builder.rectangle(
+            graphics::DrawMode::fill(),
+            graphics_rect(block.rect.contract(1.)),
+            block_color(block.kind)
+        );
+    }
+
+    builder.build(ctx).map(Some)
 }
 
 impl event::EventHandler for App {
@@ -119,10 +257,33 @@ impl event::EventHandler for App {
 
         let serve = keyboard::is_key_pressed(ctx, KEY_SERVE);
 
-        let input = game::Input { paddle_dir, serve };
+        let keys = game::Input { paddle_dir, serve };
 
         while timer::check_update_time(ctx, FRAMERATE) {
-            if !self.state.update(DT, input) {
+            let input = match &mut self.controller {
+                Some(controller) => match controller.input(self.campaign.state()) {
+                    Some(input) => input,
+                    None => {
+                        event::quit(ctx);
+                        break;
+                    }
+                },
+
+                None => keys,
+            };
+
+            if let Some(recording) = &mut self.recording {
+                recording.record(input);
+            }
+
+            let progress = self.campaign.update(DT, input);
+
+            if progress.level_cleared() {
+                self.campaign.print_level_summary();
+            }
+
+            if progress.is_over() {
+                self.campaign.print_summary();
                 event::quit(ctx);
                 break;
             }
@@ -133,61 +294,109 @@ impl event::EventHandler for App {
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
         let alpha = timer::remaining_update_time(ctx).as_secs_f32() * FRAMERATE as f32;
-        let frame = self.state.frame(alpha);
+        let frame = self.campaign.frame(alpha);
 
         graphics::clear(ctx, [0.05, 0.10, 0.15, 1.].into());
 
         let paddle_mesh = graphics::Mesh::new_rectangle(
             ctx,
             graphics::DrawMode::fill(),
-            frame.paddle_rect.into(),
+            graphics_rect(frame.paddle_rect),
             graphics::WHITE,
         )?;
         graphics::draw(ctx, &paddle_mesh, (frame.paddle_pos,))?;
 
-        let ball_mesh = graphics::Mesh::new_rectangle(
+        let ball_mesh = graphics::Mesh::new_circle(
             ctx,
             graphics::DrawMode::fill(),
-            frame.ball_rect.into(),
+            P2::origin(),
+            frame.ball_radius,
+            0.25,
             graphics::Color::new(1.0, 0.5, 0.0, 1.),
         )?;
-        graphics::draw(ctx, &ball_mesh, (frame.ball_pos,))?;
+        for ball_pos in frame.ball_positions.iter() {
+            graphics::draw(ctx, &ball_mesh, (*ball_pos,))?;
+        }
 
-        for block in frame.blocks.iter() {
+        if self.blocks_revision != Some(frame.blocks_revision) {
+            self.blocks = blocks_mesh(ctx, frame.blocks)?;
+            self.blocks_revision = Some(frame.blocks_revision);
+        }
+
+        if let Some(blocks) = &self.blocks {
+            graphics::draw(ctx, blocks, (P2::new(0., 0.), ))?;
+        }
+
+        for (id, rect) in frame.moving_blocks.iter() {
             let block_mesh = graphics::Mesh::new_rectangle(
                 ctx,
                 graphics::DrawMode::fill(),
-                block.rect.contract(1.).into(),
-                block_color(block)
+                graphics_rect(rect.contract(1.)),
+                block_color(frame.blocks[*id].kind)
             )?;
 
             graphics::draw(ctx, &block_mesh, (P2::new(0., 0.), ))?;
         }
 
         for pickup in frame.pickups {
-            let sprite = pickup_sprite(pickup.kind);
-
-            let mesh = graphics::Mesh::new_rectangle(
-                ctx,
-                graphics::DrawMode::fill(),
-                Rect::new_square_centered(sprite.size).into(),
-                sprite.color,
-            )?;
+            let sprite = self.pickup_sprite(pickup);
 
             let params = graphics::DrawParam::new()
                 .dest(pickup.position)
                 .rotation(sprite.angle);
 
-            graphics::draw(ctx, &mesh, params)?;
+            graphics::draw(ctx, sprite.mesh, params)?;
+
+            if let game::PickupKind::Bonus(amount) = pickup.kind {
+                let text = self.bonus_text(amount);
+                let (width, height) = text.dimensions(ctx);
+                let offset = V2::new(-(width as f32) * 0.5, height as f32 * 0.5);
+
+                let params = graphics::DrawParam::new()
+                    .dest(pickup.position + offset)
+                    .scale(V2::new(1., -1.))
+                    .color(graphics::BLACK);
+
+                graphics::draw(ctx, &text, params)?;
+            }
+        }
+
+        let particle_mesh = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics_rect(Rect::new_square_centered(1.)),
+            graphics::WHITE,
+        )?;
+        for particle in frame.particles {
+            let mut color = particle_color(particle.kind);
+            color.a = particle.fade();
+
+            let params = graphics::DrawParam::new()
+                .dest(particle.position(alpha))
+                .rotation(particle.angle)
+                .scale(V2::new(particle.size, particle.size))
+                .color(color);
+
+            graphics::draw(ctx, &particle_mesh, params)?;
         }
 
-        let status_line = format!(
-            "Score: {:8} Combo: x{:1.1} {:+8}",
+        let mut status_line = format!(
+            "Score: {:8} Combo: x{:1.1} {:+8} Speed: {:3.0}",
             frame.scoring.score,
             frame.scoring.combo_multiplier,
-            frame.scoring.combo_score
+            frame.scoring.combo_score,
+            frame.ball_speed
         );
 
+        for effect in frame.effects {
+            let name = match effect.kind {
+                game::EffectKind::WidePaddle   => "Wide",
+                game::EffectKind::NarrowPaddle => "Narrow",
+                game::EffectKind::Catch        => "Catch",
+            };
+            status_line += &format!(" {} x{} {:2.0}s", name, effect.stacks, effect.remaining.ceil());
+        }
+
         let mut text = graphics::Text::new(status_line);
         text.set_font(self.font, graphics::Scale::uniform(20.));
 
@@ -200,16 +409,64 @@ impl event::EventHandler for App {
                 .scale(V2::new(1., -1.)),
         )?;
 
+        for life in 0 .. frame.lives {
+            let pos = P2::new(frame.rect.maxs.x - 12. - life as f32 * 16., frame.rect.mins.y + 12.);
+            graphics::draw(ctx, &self.heart, (pos,))?;
+        }
+
         graphics::present(ctx)?;
         timer::yield_now();
         Ok(())
     }
 }
 
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
 pub fn main() -> GameResult {
-    let seed = rand::rngs::OsRng.next_u64();
-    let state = game::State::new(seed);
-    let rect = state.rect();
+    let mut seed = rand::rngs::OsRng.next_u64();
+
+    let mut levels = Vec::new();
+    let mut record_path = None;
+    let mut replay_path = None;
+    let mut attract = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--record"  => record_path = Some(args.next().unwrap_or_else(|| fail("--record needs a file"))),
+            "--replay"  => replay_path = Some(args.next().unwrap_or_else(|| fail("--replay needs a file"))),
+            "--attract" => attract = true,
+            _           => levels.push(game::LevelSource::parse(&arg)),
+        }
+    }
+
+    let mut controller: Option<Box<dyn game::Controller>> = None;
+    if let Some(path) = &replay_path {
+        let replay = game::Replay::load(path)
+            .unwrap_or_else(|err| fail(format!("{}: {}", path, err)));
+        seed = replay.seed;
+        levels = replay.levels.clone();
+        controller = Some(Box::new(replay.playback()));
+    }
+    else if attract {
+        controller = Some(Box::new(game::Autopilot::new(true)));
+    }
+
+    if levels.is_empty() {
+        levels = (0 .. CAMPAIGN_LENGTH)
+            .map(|_| game::LevelSource::Procedural(rand::rngs::OsRng.next_u64()))
+            .collect();
+    }
+
+    let campaign = game::Campaign::new(seed, &levels)
+        .unwrap_or_else(|err| fail(err));
+
+    let recording = record_path.as_ref()
+        .map(|_| game::Replay::new(seed, campaign.sources()));
+    let rect = campaign.rect();
 
     let window_mode = ggez::conf::WindowMode {
         width:  rect.width(),
@@ -235,7 +492,7 @@ pub fn main() -> GameResult {
     let mut ctx_builder = ggez::ContextBuilder::new("breakout", "ggez");
 
     if let Ok(manifest_dir) = std::env::var("CARGO_MANIFEST_DIR") {
-        let mut path = std::path::PathBuf::from(manifest_dir);
+        let path = std::path::PathBuf::from(manifest_dir);
         ctx_builder = ctx_builder.add_resource_path(path);
     }
 
@@ -244,12 +501,23 @@ pub fn main() -> GameResult {
         .window_setup(window_setup)
         .build()?;
 
-    let mut screen_rect = graphics::Rect::from(rect);
+    let mut screen_rect = graphics_rect(rect);
     screen_rect.y = screen_rect.h;
     screen_rect.h *= -1.;
     graphics::set_screen_coordinates(ctx, screen_rect)?;
 
-    let app = &mut App::new(ctx, state)?;
-    event::run(ctx, event_loop, app)
+    let app = &mut App::new(ctx, campaign)?;
+    app.controller = controller;
+    app.recording  = recording;
+
+    let result = event::run(ctx, event_loop, app);
+
+    if let (Some(recording), Some(path)) = (&app.recording, &record_path) {
+        if let Err(err) = recording.save(path) {
+            eprintln!("{}: {}", path, err);
+        }
+    }
+
+    result
 }
 
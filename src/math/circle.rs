@@ -0,0 +1,42 @@
+
+use {
+    super::*,
+    crate::dilate::Dilate,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Circle {
+    pub center: P2,
+    pub radius: f32,
+}
+
+impl Circle {
+    pub fn new(center: P2, radius: f32) -> Circle {
+        debug_assert!(radius >= 0.);
+        Circle { center, radius }
+    }
+
+    pub fn new_centered(radius: f32) -> Circle {
+        Circle::new(P2::origin(), radius)
+    }
+
+    pub fn at(&self, origin: P2) -> Circle {
+        Circle::new(self.center + origin.coords, self.radius)
+    }
+
+    pub fn bounds(&self) -> Rect {
+        Rect::new_square_centered(self.radius * 2.).at(self.center)
+    }
+}
+
+impl Dilate<f32> for Circle {
+    type Output = Circle;
+
+    fn expand(&self, by: f32) -> Circle {
+        Circle::new(self.center, self.radius + by)
+    }
+
+    fn contract(&self, by: f32) -> Circle {
+        Circle::new(self.center, (self.radius - by).max(0.))
+    }
+}
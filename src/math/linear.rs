@@ -144,7 +144,7 @@ impl Segment {
 
 impl Linear for Segment {
     fn whole_line(&self) -> Line { self.0 }
-    fn parameter_on(&self, t: f32) -> bool { t >= 0. && t <= 1. }
+    fn parameter_on(&self, t: f32) -> bool { (0. ..= 1.).contains(&t) }
     fn relative_to(&self, other: impl Linear) -> Segment {
         Segment(self.whole_line().relative_to(other))
     }
@@ -1,27 +1,35 @@
 
 pub mod rect;
 pub mod linear;
+pub mod circle;
 
-pub use rect::Rect;
+pub use rect::{Rect, RoundedRect};
 pub use linear::*;
+pub use circle::Circle;
 
 use {
     crate::collider::Collider,
-    ggez::nalgebra as na,
+    nalgebra as na,
 };
 
 pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
     a + t * (b - a)
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct OrdF32(pub f32);
 
 impl Eq for OrdF32 { }
 
+impl PartialOrd for OrdF32 {
+    fn partial_cmp(&self, rhs: &OrdF32) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+
 impl Ord for OrdF32 {
     fn cmp(&self, rhs: &OrdF32) -> std::cmp::Ordering {
-        self.partial_cmp(rhs)
+        self.0.partial_cmp(&rhs.0)
             .unwrap_or(std::cmp::Ordering::Equal)
     }
 }
@@ -41,3 +49,7 @@ pub fn reflect(v: V2, n: V2) -> V2 {
     v - 2. * n.dot(&v) * n
 }
 
+pub fn rotate(v: V2, angle: f32) -> V2 {
+    na::Rotation2::new(angle) * v
+}
+
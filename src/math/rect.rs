@@ -2,7 +2,7 @@
 use {
     super::*,
     crate::{
-        collider::Collider,
+        collider::{Arc, Collider},
         dilate::Dilate,
     },
 };
@@ -13,12 +13,20 @@ pub enum CollideFrom {
     Outside
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Rect {
     pub mins: P2,
     pub maxs: P2
 }
 
+// A rect grown by `radius` all round, with rounded corners; what a circle's centre
+// sweeps out moving around the outside of `rect`.
+#[derive(Clone, Copy, Debug)]
+pub struct RoundedRect {
+    pub rect:   Rect,
+    pub radius: f32,
+}
+
 impl Rect {
     pub fn new_unchecked(mins: P2, maxs: P2) -> Rect {
         debug_assert!(mins.x <= maxs.x);
@@ -65,6 +73,29 @@ impl Rect {
         && (self.mins.y .. self.maxs.y).contains(&p.y)
     }
 
+    pub fn overlaps(&self, other: &Rect) -> bool {
+           self.mins.x <= other.maxs.x && other.mins.x <= self.maxs.x
+        && self.mins.y <= other.maxs.y && other.mins.y <= self.maxs.y
+    }
+
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect::new_unchecked(
+            P2::new(self.mins.x.min(other.mins.x), self.mins.y.min(other.mins.y)),
+            P2::new(self.maxs.x.max(other.maxs.x), self.maxs.y.max(other.maxs.y))
+        )
+    }
+
+    pub fn closest_point(&self, p: P2) -> P2 {
+        P2::new(
+            p.x.max(self.mins.x).min(self.maxs.x),
+            p.y.max(self.mins.y).min(self.maxs.y)
+        )
+    }
+
+    pub fn distance_to(&self, p: P2) -> f32 {
+        (self.closest_point(p) - p).norm()
+    }
+
     pub fn vertices(&self) -> [P2; 4] {
         [   self.mins,
             P2::new(self.maxs.x, self.mins.y),
@@ -107,6 +138,43 @@ impl Rect {
     }
 }
 
+impl RoundedRect {
+    // The corners are only rounded when colliding from outside; from inside, the
+    // straight sides are extended to meet, so the corners can never be reached.
+    pub fn to_collider(self, from: CollideFrom) -> Collider {
+        if from == CollideFrom::Inside {
+            return self.rect.expand(self.radius).to_collider(from);
+        }
+
+        let sides = self.rect.sides();
+        let normals = sides.iter()
+            .map(|side| right(side.direction()))
+            .collect::<Vec<_>>();
+
+        let edges = sides.iter()
+            .zip(normals.iter())
+            .map(|(side, normal)| Segment::new(side.source() + normal * self.radius, side.stride()))
+            .collect();
+
+        if self.radius <= 0. {
+            return Collider::new(edges);
+        }
+
+        // each corner turns from the normal of the side before it to the one after
+        let arcs = self.rect.vertices().iter()
+            .enumerate()
+            .map(|(i, vertex)| Arc {
+                center: *vertex,
+                radius: self.radius,
+                from:   normals[(i + 3) % 4],
+                to:     normals[i],
+            })
+            .collect();
+
+        Collider::with_arcs(edges, arcs)
+    }
+}
+
 impl Dilate<Rect> for Rect {
     type Output = Rect;
 
@@ -133,8 +201,14 @@ impl Dilate<f32> for Rect {
     }
 }
 
-impl From<Rect> for ggez::graphics::Rect {
-    fn from(rect: Rect) -> Self {
-        ggez::graphics::Rect::new(rect.mins.x, rect.mins.y, rect.width(), rect.height())
+impl Dilate<Circle> for Rect {
+    type Output = RoundedRect;
+
+    fn expand(&self, by: Circle) -> RoundedRect {
+        RoundedRect { rect: self.at(by.center), radius: by.radius }
+    }
+
+    fn contract(&self, by: Circle) -> RoundedRect {
+        RoundedRect { rect: self.contract(by.radius).at(by.center), radius: 0. }
     }
 }